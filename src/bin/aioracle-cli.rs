@@ -0,0 +1,130 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{from_slice, to_vec, Binary};
+
+use aioracle::merkle::{decode_hash, verify_proof, MerkleTree};
+use aioracle::state::{HashAlgorithm, TreeFormat};
+
+const USAGE: &str = "Usage:
+    aioracle-cli root <reports.json> [options]
+    aioracle-cli proofs <reports.json> [options]
+    aioracle-cli verify <root> <data> [<proof hash>...] [options]
+
+The reports file holds a JSON array of strings, one leaf per executor report.
+
+Options:
+    --hash <sha256|keccak256>    hash function of the tree, sha256 by default
+    --format <legacy|v1>         leaf and node hashing of the tree, v1 by default as for new
+                                 services";
+
+/// Proof of a leaf, `data` and `proof` go as is into `QueryMsg::VerifyData`.
+#[cw_serde]
+struct LeafProof {
+    index: usize,
+    data: Binary,
+    proof: Vec<String>,
+}
+
+struct Options {
+    args: Vec<String>,
+    algorithm: HashAlgorithm,
+    format: TreeFormat,
+}
+
+fn parse_options(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        args: vec![],
+        algorithm: HashAlgorithm::Sha256,
+        format: TreeFormat::V1,
+    };
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--hash" => {
+                options.algorithm = match args.next().as_deref() {
+                    Some("sha256") => HashAlgorithm::Sha256,
+                    Some("keccak256") => HashAlgorithm::Keccak256,
+                    _ => return Err("--hash expects sha256 or keccak256".to_string()),
+                }
+            }
+            "--format" => {
+                options.format = match args.next().as_deref() {
+                    Some("legacy") => TreeFormat::Legacy,
+                    Some("v1") => TreeFormat::V1,
+                    _ => return Err("--format expects legacy or v1".to_string()),
+                }
+            }
+            _ => options.args.push(arg),
+        }
+    }
+    Ok(options)
+}
+
+fn read_reports(path: &str) -> Result<Vec<String>, String> {
+    let content = fs::read(path).map_err(|err| format!("cannot read {}: {}", path, err))?;
+    let reports: Vec<String> =
+        from_slice(&content).map_err(|err| format!("invalid reports in {}: {}", path, err))?;
+    if reports.is_empty() {
+        return Err(format!("no reports in {}", path));
+    }
+    Ok(reports)
+}
+
+fn run(command: &str, options: Options) -> Result<(), String> {
+    let Options {
+        args,
+        algorithm,
+        format,
+    } = options;
+    match (command, args.as_slice()) {
+        ("root", [path]) => {
+            let reports = read_reports(path)?;
+            let tree = MerkleTree::new(algorithm, format, &reports);
+            println!("{}", tree.hex_root().unwrap_or_default());
+        }
+        ("proofs", [path]) => {
+            let reports = read_reports(path)?;
+            let tree = MerkleTree::new(algorithm, format, &reports);
+            let proofs = reports
+                .into_iter()
+                .enumerate()
+                .map(|(index, report)| LeafProof {
+                    index,
+                    data: Binary::from(report.into_bytes()),
+                    proof: tree.hex_proof(index).unwrap_or_default(),
+                })
+                .collect::<Vec<LeafProof>>();
+            let proofs = to_vec(&proofs).map_err(|err| err.to_string())?;
+            println!("{}", String::from_utf8_lossy(&proofs));
+        }
+        ("verify", [root, data, proof @ ..]) => {
+            let root = decode_hash(root).map_err(|_| "invalid root".to_string())?;
+            let proof = proof
+                .iter()
+                .map(|hash| decode_hash(hash))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| "invalid proof".to_string())?;
+            if !verify_proof(algorithm, format, &root, data.as_bytes(), &proof) {
+                return Err("not verified".to_string());
+            }
+            println!("verified");
+        }
+        _ => return Err(USAGE.to_string()),
+    }
+    Ok(())
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let result = match args.next() {
+        Some(command) => parse_options(args).and_then(|options| run(&command, options)),
+        None => Err(USAGE.to_string()),
+    };
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
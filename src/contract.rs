@@ -1,27 +1,53 @@
 use cosmwasm_std::{
-    attr, entry_point, to_binary, Binary, CanonicalAddr, Decimal, Deps, DepsMut, Env, MessageInfo,
-    Response, StdError, StdResult, Uint128,
+    attr, entry_point, to_binary, Addr, Api, BankMsg, Binary, CanonicalAddr, Coin, Decimal, Deps,
+    DepsMut, Env, MessageInfo, Order, Reply, Response, StdError, StdResult, Storage, SubMsg,
+    Uint128, WasmMsg,
 };
 
 use cw2::set_contract_version;
+use cw_storage_plus::Bound;
 
-use sha2::Digest;
-use std::convert::TryInto;
+use std::collections::BTreeMap;
 use std::ops::Mul;
 
 use crate::error::ContractError;
-use crate::executors::{query_executor_size, query_executors, remove_executors, store_executors};
+use crate::executors::{
+    add_coin, apply_executor_changes, distribute_rewards, executor_key_signing_hash,
+    merkle_root_signing_hash, migrate_executors, query_allowed_executor_size,
+    query_epoch_executors, query_executor, query_executor_application, query_executor_applications,
+    query_executor_bond, query_executor_in_list, query_executor_rewards, query_executor_size,
+    query_executors, queue_executor_changes, slash_executors, store_executors,
+    update_executor_status, update_executors_status, update_open_challenges,
+    verify_executor_signature, verify_signature,
+};
 
+use crate::merkle::{decode_hash, verify_multiproof, verify_proof, Hash};
 use crate::msg::{
-    AddServiceMsg, ExecuteMsg, InstantiateMsg, LatestStageResponse, MigrateMsg, QueryMsg,
-    RequestResponse, ServiceInfoResponse, UpdateConfigMsg, UpdateServiceMsg,
+    AddServiceMsg, CallbackMsg, ExecuteMsg, ExecutorSignature, InstantiateMsg, LatestStageResponse,
+    MigrateMsg, QueryMsg, RequestCallback, RequestFilter, RequestListResponse, RequestResponse,
+    RequestResultResponse, SearchRequestsResponse, ServiceFeesResponse, ServiceListResponse,
+    UpdateConfigMsg, UpdateServiceMsg, VerifyDataBatchResponse, VerifyDataItem, VerifyDataResponse,
 };
 use crate::state::{
-    config_read, config_save, config_update, get_range_params, latest_stage_read,
-    latest_stage_save, latest_stage_update, read_executor, read_service_info, read_service_infos,
-    remove_service_info, requests, store_service_info, Config, Request, ServiceInfo,
+    are_requests_migrated, config_read, config_save, config_update, executor_set_version_read,
+    get_range_params, has_executor_bonds, is_executor_in_set, latest_stage_read, latest_stage_save,
+    latest_stage_update, match_order, migrate_config, migrate_counters, migrate_executor_bonds,
+    migrate_executor_set, open_challenge_count_read, range_after, read_challenge, read_executor,
+    read_executor_application, read_executor_bond, read_executor_rewards, read_merkle_root_signers,
+    read_request_results, read_service_info, read_service_infos, remove_executor_application,
+    remove_executor_rewards, remove_merkle_root_signers, remove_request_results,
+    remove_service_info, request_migration_read, request_migration_save,
+    requester_request_count_read, requester_request_count_save, requests, service_count_read,
+    service_request_count_read, service_request_count_save, store_challenge, store_executor,
+    store_executor_application, store_executor_bond, store_merkle_root_signers,
+    store_request_results, store_service_info, Callback, Challenge, Config, ExecutorApplication,
+    Request, RequestMigration, RequestStatus, Service, ServiceInfo, TreeFormat, Unbonding,
+    DEFAULT_LIMIT, DEFAULT_MIGRATE_LIMIT, MAX_RESULTS, MAX_RESULT_SIZE, MAX_SEARCH_SCAN,
+    MAX_VERIFY_BATCH,
 };
 pub const MAXIMUM_REQ_THRESHOLD: u64 = 67;
+/// Gas a request callback may use, so running out of gas fails the callback alone.
+pub const CALLBACK_GAS_LIMIT: u64 = 1_000_000;
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:aioracle-v2";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -29,20 +55,48 @@ const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: InstantiateMsg,
-) -> StdResult<Response> {
+) -> Result<Response, ContractError> {
     let owner = msg.owner.unwrap_or(info.sender);
+    let slash_fraction = msg.slash_fraction.unwrap_or_default();
+    if slash_fraction > Decimal::one() {
+        return Err(ContractError::InvalidSlashFraction {});
+    }
+    // executors must not get their bond out before a wrong root they registered is challenged
+    let challenge_period = msg.challenge_period.unwrap_or_default();
+    let unbonding_period = msg.unbonding_period.unwrap_or_default();
+    if unbonding_period < challenge_period {
+        return Err(ContractError::InvalidUnbondingPeriod {});
+    }
 
     let config = Config {
         owner,
         max_req_threshold: MAXIMUM_REQ_THRESHOLD,
+        request_timeout: msg.request_timeout,
+        challenge_period,
+        challenge_deposit: msg.challenge_deposit,
+        min_executor_bond: msg.min_executor_bond,
+        unbonding_period,
+        slash_fraction,
+        jail_period: msg.jail_period.unwrap_or_default(),
+        burn_slashed: msg.burn_slashed.unwrap_or_default(),
+        owner_registration: msg.owner_registration.unwrap_or_default(),
+        epoch_length: msg.epoch_length.unwrap_or_default(),
     };
     config_save(deps.storage, &config)?;
 
     let stage = 0;
     latest_stage_save(deps.storage, &stage)?;
+    // nothing stored before this version to migrate
+    request_migration_save(
+        deps.storage,
+        &RequestMigration {
+            next_stage: 1,
+            last_stage: 0,
+        },
+    )?;
 
     // first nonce
     // let mut executor_index = 0;
@@ -51,11 +105,10 @@ pub fn instantiate(
             deps.storage,
             executors
                 .into_iter()
-                .map(|ex| Ok(deps.api.addr_canonicalize(&ex)?))
+                .map(|ex| deps.api.addr_canonicalize(&ex))
                 .collect::<StdResult<Vec<CanonicalAddr>>>()?,
-        );
-    } else {
-        store_executors(deps.storage, vec![])
+            env.block.height,
+        )?;
     }
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
     Ok(Response::default())
@@ -68,6 +121,7 @@ pub fn execute(
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
+    apply_executor_changes(deps.storage, env.block.height)?;
     match msg {
         ExecuteMsg::UpdateConfig { update_config_msg } => {
             execute_update_config(deps, env, info, update_config_msg)
@@ -77,21 +131,78 @@ pub fn execute(
             merkle_root,
             executors,
         } => execute_register_merkle_root(deps, env, info, stage, merkle_root, executors),
+        ExecuteMsg::SubmitMerkleRoot {
+            stage,
+            merkle_root,
+            signatures,
+        } => execute_submit_merkle_root(deps, env, stage, merkle_root, signatures),
         ExecuteMsg::Request {
             service,
             input,
             threshold,
-        } => handle_request(deps, info, env, service, input, threshold),
+            callback,
+        } => handle_request(deps, info, env, service, input, threshold, callback),
         ExecuteMsg::AddService(service_msg) => handle_add_service(deps, info, service_msg),
         ExecuteMsg::UpdateService(service_msg) => handle_update_service(deps, info, service_msg),
         ExecuteMsg::DeleteService { service_name } => {
             handle_delete_service(deps, info, service_name)
         }
+        ExecuteMsg::ClaimReward {} => handle_claim_reward(deps, info),
+        ExecuteMsg::RotateExecutorKey { pubkey, signature } => {
+            handle_rotate_executor_key(deps, env, info, pubkey, signature)
+        }
+        ExecuteMsg::UpdateExecutorMetadata { metadata } => {
+            handle_update_executor_metadata(deps, info, metadata)
+        }
+        ExecuteMsg::BondExecutor {} => handle_bond_executor(deps, info),
+        ExecuteMsg::UnbondExecutor { amount } => handle_unbond_executor(deps, env, info, amount),
+        ExecuteMsg::WithdrawUnbonded {} => handle_withdraw_unbonded(deps, env, info),
+        ExecuteMsg::UnjailExecutor {} => handle_unjail_executor(deps, env, info),
+        ExecuteMsg::ReclaimRequest { stage } => handle_reclaim_request(deps, env, info, stage),
+        ExecuteMsg::CancelRequest { stage } => handle_cancel_request(deps, info, stage),
+        ExecuteMsg::FinalizeMerkleRoot { stage } => handle_finalize_merkle_root(deps, env, stage),
+        ExecuteMsg::ChallengeMerkleRoot { stage, evidence } => {
+            handle_challenge_merkle_root(deps, env, info, stage, evidence)
+        }
+        ExecuteMsg::ResolveChallenge { stage, upheld } => {
+            handle_resolve_challenge(deps, env, info, stage, upheld)
+        }
+        ExecuteMsg::SubmitResult { stage, data, proof } => {
+            handle_submit_result(deps, info, stage, data, proof)
+        }
+        ExecuteMsg::ApplyAsExecutor {
+            pubkey,
+            signature,
+            metadata,
+        } => handle_apply_as_executor(deps, env, info, pubkey, signature, metadata),
+        ExecuteMsg::ApproveExecutor { applicant } => {
+            handle_approve_executor(deps, env, info, applicant)
+        }
+        ExecuteMsg::RejectExecutor { applicant } => handle_reject_executor(deps, info, applicant),
+        ExecuteMsg::MigrateRequests { limit } => handle_migrate_requests(deps, info, limit),
     }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> StdResult<Response> {
+pub fn migrate(deps: DepsMut, env: Env, _msg: MigrateMsg) -> StdResult<Response> {
+    migrate_config(deps.storage)?;
+    migrate_executors(deps.storage, env.block.height)?;
+    migrate_executor_bonds(deps.storage)?;
+
+    // the stored requests are migrated in batches with ExecuteMsg::MigrateRequests
+    if request_migration_read(deps.storage)?.is_none() {
+        let last_stage = latest_stage_read(deps.storage)?;
+        request_migration_save(
+            deps.storage,
+            &RequestMigration {
+                next_stage: 1,
+                last_stage,
+            },
+        )?;
+    }
+    migrate_counters(deps.storage)?;
+    migrate_executor_set(deps.storage)?;
+
     // once we have "migrated", set the new version and return success
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
     Ok(Response::new().add_attributes(vec![
@@ -100,6 +211,60 @@ pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> StdResult<Response
     ]))
 }
 
+pub fn handle_migrate_requests(
+    deps: DepsMut,
+    info: MessageInfo,
+    limit: Option<u64>,
+) -> Result<Response, ContractError> {
+    if info.sender != config_read(deps.storage)?.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let mut migration = request_migration_read(deps.storage)?
+        .filter(|migration| migration.next_stage <= migration.last_stage)
+        .ok_or(ContractError::NothingToMigrate {})?;
+    let limit = limit.unwrap_or(DEFAULT_MIGRATE_LIMIT).max(1);
+    let end_stage = migration
+        .last_stage
+        .min(migration.next_stage.saturating_add(limit - 1));
+
+    // requests stored before the status field existed are pending unless they have a merkle root,
+    // saving them again also fills the status index
+    let stored_requests = requests()
+        .range(
+            deps.storage,
+            Some(Bound::inclusive(migration.next_stage)),
+            Some(Bound::inclusive(end_stage)),
+            Order::Ascending,
+        )
+        .collect::<StdResult<Vec<(u64, Request)>>>()?;
+    let mut service_requests: BTreeMap<String, u64> = BTreeMap::new();
+    let mut requester_requests: BTreeMap<Addr, u64> = BTreeMap::new();
+    for (stage, mut request) in stored_requests {
+        request.status = stored_request_status(&request);
+        *service_requests.entry(request.service.clone()).or_default() += 1;
+        *requester_requests
+            .entry(request.requester.clone())
+            .or_default() += 1;
+        requests().save(deps.storage, stage, &request)?;
+    }
+    for (service, count) in service_requests {
+        let service_requests = service_request_count_read(deps.storage, &service);
+        service_request_count_save(deps.storage, &service, service_requests + count)?;
+    }
+    for (requester, count) in requester_requests {
+        let requester_requests = requester_request_count_read(deps.storage, &requester);
+        requester_request_count_save(deps.storage, &requester, requester_requests + count)?;
+    }
+    migration.next_stage = end_stage + 1;
+    request_migration_save(deps.storage, &migration)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "migrate_requests"),
+        attr("migrated_until", end_stage.to_string()),
+        attr("remaining", (migration.last_stage - end_stage).to_string()),
+    ]))
+}
+
 pub fn handle_add_service(
     deps: DepsMut,
     info: MessageInfo,
@@ -109,12 +274,15 @@ pub fn handle_add_service(
     if service_info.is_some() {
         return Err(ContractError::ServiceExists {});
     }
+    let mut service = service_msg.service;
+    service.tree_format = Some(service.tree_format.unwrap_or(TreeFormat::V1));
     store_service_info(
         deps.storage,
         service_msg.service_name.as_bytes(),
         &ServiceInfo {
             owner: info.sender,
-            service: service_msg.service,
+            service,
+            allowed_executors: None,
         },
     )?;
     Ok(Response::new().add_attributes(vec![
@@ -145,6 +313,29 @@ pub fn handle_update_service(
         if let Some(oscript_url) = service_msg.oscript_url {
             service_info.service.oscript_url = oscript_url;
         }
+        if let Some(fee) = service_msg.fee {
+            service_info.service.fee = Some(fee);
+        }
+        if let Some(request_timeout) = service_msg.request_timeout {
+            service_info.service.request_timeout = Some(request_timeout);
+        }
+        if let Some(hash_algorithm) = service_msg.hash_algorithm {
+            service_info.service.hash_algorithm = hash_algorithm;
+        }
+        if let Some(tree_format) = service_msg.tree_format {
+            service_info.service.tree_format = Some(tree_format);
+        }
+        if let Some(executors) = service_msg.allowed_executors {
+            let mut allowed_executors: Vec<Addr> = vec![];
+            for executor in executors {
+                let executor = deps.api.addr_validate(&executor)?;
+                if !allowed_executors.contains(&executor) {
+                    allowed_executors.push(executor);
+                }
+            }
+            service_info.allowed_executors =
+                Some(allowed_executors).filter(|executors| !executors.is_empty());
+        }
         store_service_info(
             deps.storage,
             service_msg.service_name.as_bytes(),
@@ -169,56 +360,86 @@ pub fn handle_delete_service(
         if service_info.owner.ne(&info.sender) {
             return Err(ContractError::Unauthorized {});
         }
-        remove_service_info(deps.storage, service_name.as_bytes());
+        remove_service_info(deps.storage, service_name.as_bytes())?;
         return Ok(Response::new().add_attributes(vec![
             attr("action", "delete_service"),
             attr("service_name", service_name),
         ]));
     }
 
-    return Err(ContractError::Unauthorized {});
+    Err(ContractError::Unauthorized {})
 }
 
 pub fn execute_update_config(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     update_config_msg: UpdateConfigMsg,
 ) -> Result<Response, ContractError> {
     // authorize owner
-    let UpdateConfigMsg {
-        new_owner,
-        new_executors,
-        old_executors,
-        new_max_req_threshold,
-        ..
-    } = update_config_msg;
     let cfg = config_read(deps.storage)?;
     let owner = cfg.owner;
     if info.sender != owner {
         return Err(ContractError::Unauthorized {});
     }
 
+    if update_config_msg
+        .new_slash_fraction
+        .is_some_and(|slash_fraction| slash_fraction > Decimal::one())
+    {
+        return Err(ContractError::InvalidSlashFraction {});
+    }
+    if update_config_msg
+        .new_unbonding_period
+        .unwrap_or(cfg.unbonding_period)
+        < update_config_msg
+            .new_challenge_period
+            .unwrap_or(cfg.challenge_period)
+    {
+        return Err(ContractError::InvalidUnbondingPeriod {});
+    }
+
+    // bonds are held in a single denom, it cannot change under them
+    if let Some(min_executor_bond) = &update_config_msg.new_min_executor_bond {
+        let bond_denom = cfg
+            .min_executor_bond
+            .as_ref()
+            .map(|min_executor_bond| min_executor_bond.denom.as_str());
+        if bond_denom != Some(min_executor_bond.denom.as_str()) && has_executor_bonds(deps.storage)
+        {
+            return Err(ContractError::BondDenomLocked {});
+        }
+    }
+
     // if owner some validated to addr, otherwise set to none
-    config_update(deps.storage, new_owner, new_max_req_threshold)?;
+    config_update(deps.storage, &update_config_msg)?;
+    let UpdateConfigMsg {
+        new_executors,
+        old_executors,
+        new_min_executor_bond,
+        ..
+    } = update_config_msg;
+    if let Some(min_executor_bond) = new_min_executor_bond {
+        if cfg.min_executor_bond.as_ref() != Some(&min_executor_bond) {
+            update_executors_status(deps.storage)?;
+        }
+    }
 
-    if let Some(executors) = new_executors {
-        store_executors(
+    if new_executors.is_some() || old_executors.is_some() {
+        queue_executor_changes(
             deps.storage,
-            executors
+            new_executors
+                .unwrap_or_default()
                 .into_iter()
                 .map(|executor| deps.api.addr_canonicalize(&executor))
                 .collect::<StdResult<Vec<CanonicalAddr>>>()?,
-        );
-    }
-    if let Some(executors) = old_executors {
-        remove_executors(
-            deps.storage,
-            executors
+            old_executors
+                .unwrap_or_default()
                 .into_iter()
                 .map(|executor| deps.api.addr_canonicalize(&executor))
                 .collect::<StdResult<Vec<CanonicalAddr>>>()?,
-        );
+            env.block.height,
+        )?;
     }
 
     Ok(Response::new().add_attributes(vec![attr("action", "update_config")]))
@@ -231,14 +452,43 @@ pub fn handle_request(
     service: String,
     input: Option<String>,
     threshold: u64,
+    callback: Option<RequestCallback>,
 ) -> Result<Response, ContractError> {
     let stage = latest_stage_update(deps.storage)?;
     let Config {
-        max_req_threshold, ..
+        max_req_threshold,
+        request_timeout,
+        ..
     } = config_read(deps.storage)?;
 
+    let service_info = read_service_info(deps.storage, service.as_bytes())
+        .map_err(|_| ContractError::ServiceNotFound {})?;
+
+    // the fees stay in the contract with the request, anything sent on top is given back
+    let fees = get_service_fees(&service_info.service)?;
+    let surplus = deduct_fees(&fees, &info.funds)?;
+
+    let timeout_height = service_info
+        .service
+        .request_timeout
+        .or(request_timeout)
+        .map(|timeout| env.block.height.saturating_add(timeout));
+
+    let callback = callback
+        .map(|RequestCallback { contract, msg }| -> StdResult<Callback> {
+            Ok(Callback {
+                contract: deps.api.addr_validate(&contract)?,
+                msg,
+            })
+        })
+        .transpose()?;
+
     // this will keep track of the executor list of the request
-    let current_size = query_executor_size(deps.as_ref());
+    let current_size = match &service_info.allowed_executors {
+        Some(allowed_executors) => query_allowed_executor_size(deps.as_ref(), allowed_executors)?,
+        None => query_executor_size(deps.as_ref(), env.block.height)?,
+    };
+    let executor_set_version = executor_set_version_read(deps.storage);
 
     if Uint128::from(current_size)
         .mul(Decimal::from_ratio(
@@ -261,10 +511,33 @@ pub fn handle_request(
             threshold,
             service: service.clone(),
             input,
+            fees,
+            timeout_height,
+            status: RequestStatus::Pending,
+            executors: vec![],
+            callback,
+            hash_algorithm: service_info.service.hash_algorithm,
+            tree_format: service_info.service.tree_format.unwrap_or_default(),
+            executor_set_version: Some(executor_set_version),
+            allowed_executors: service_info.allowed_executors,
+            challenge_end_height: None,
         },
     )?;
 
-    Ok(Response::new().add_attributes(vec![
+    let service_requests = service_request_count_read(deps.storage, &service);
+    service_request_count_save(deps.storage, &service, service_requests + 1)?;
+    let requester_requests = requester_request_count_read(deps.storage, &info.sender);
+    requester_request_count_save(deps.storage, &info.sender, requester_requests + 1)?;
+
+    let mut response = Response::new();
+    if !surplus.is_empty() {
+        response = response.add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: surplus,
+        });
+    }
+
+    Ok(response.add_attributes(vec![
         attr("action", "handle_request"),
         attr("stage", stage.to_string()),
         attr("threshold", threshold.to_string()),
@@ -272,255 +545,3792 @@ pub fn handle_request(
     ]))
 }
 
-pub fn execute_register_merkle_root(
+/// Whether the executor was active when the request was made and is allowed by its service,
+/// requests made before executor sets were versioned fall back to the current set.
+fn is_request_executor(
+    storage: &dyn Storage,
+    request: &Request,
+    executor: &Addr,
+    executor_canonical: &CanonicalAddr,
+) -> bool {
+    if let Some(allowed_executors) = &request.allowed_executors {
+        if !allowed_executors.contains(executor) {
+            return false;
+        }
+    }
+    // jailed executors are left out of the requests made before their jailing too
+    let executor_info = read_executor(storage, executor_canonical);
+    if executor_info
+        .as_ref()
+        .is_some_and(|executor_info| executor_info.jailed_until.is_some())
+    {
+        return false;
+    }
+    match request.executor_set_version {
+        Some(version) => is_executor_in_set(storage, executor_canonical, version),
+        None => executor_info.is_some_and(|executor_info| executor_info.is_active),
+    }
+}
+
+fn is_request_timed_out(request: &Request, env: &Env) -> bool {
+    request
+        .timeout_height
+        .is_some_and(|timeout_height| env.block.height >= timeout_height)
+}
+
+/// Stored status of the request. Requests stored before statuses read as pending until
+/// `MigrateRequests` reaches them, those with a merkle root are finalized already.
+fn stored_request_status(request: &Request) -> RequestStatus {
+    match request.status {
+        RequestStatus::Pending if !request.merkle_root.is_empty() => RequestStatus::Finalized,
+        ref status => status.clone(),
+    }
+}
+
+/// Status reported by queries, a pending request is expired as soon as it times out even if
+/// the requester has not reclaimed its fees yet.
+fn request_status(request: &Request, env: &Env) -> RequestStatus {
+    match stored_request_status(request) {
+        RequestStatus::Pending if is_request_timed_out(request, env) => RequestStatus::Expired,
+        status => status,
+    }
+}
+
+/// Makes sure the request is still waiting for a merkle root.
+fn assert_request_pending(request: &Request, env: &Env) -> Result<(), ContractError> {
+    match stored_request_status(request) {
+        RequestStatus::Pending if is_request_timed_out(request, env) => {
+            Err(ContractError::RequestExpired {})
+        }
+        RequestStatus::Pending => Ok(()),
+        RequestStatus::Expired => Err(ContractError::RequestExpired {}),
+        _ => Err(ContractError::AlreadyFinished {}),
+    }
+}
+
+/// Closes a pending request with the given status and sends the escrowed fees back to the requester.
+fn refund_request(
+    deps: DepsMut,
+    stage: u64,
+    mut request: Request,
+    status: RequestStatus,
+) -> Result<Response, ContractError> {
+    let refund = std::mem::take(&mut request.fees);
+    let requester = request.requester.to_string();
+    request.status = status;
+    requests().save(deps.storage, stage, &request)?;
+
+    let mut response = Response::new();
+    if !refund.is_empty() {
+        response = response.add_message(BankMsg::Send {
+            to_address: requester,
+            amount: refund,
+        });
+    }
+    Ok(response)
+}
+
+pub fn handle_reclaim_request(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     stage: u64,
-    mroot: String,
-    executors: Vec<String>,
 ) -> Result<Response, ContractError> {
-    let Config { owner, .. } = config_read(deps.storage)?;
+    let request = requests().load(deps.storage, stage)?;
+    if request.requester != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if stored_request_status(&request) != RequestStatus::Pending {
+        return Err(ContractError::AlreadyFinished {});
+    }
+    if !is_request_timed_out(&request, &env) {
+        return Err(ContractError::RequestNotExpired {});
+    }
+
+    let response = refund_request(deps, stage, request, RequestStatus::Expired)?;
+    Ok(response.add_attributes(vec![
+        attr("action", "reclaim_request"),
+        attr("stage", stage.to_string()),
+    ]))
+}
 
-    // if owner set validate, otherwise unauthorized
+pub fn handle_cancel_request(
+    deps: DepsMut,
+    info: MessageInfo,
+    stage: u64,
+) -> Result<Response, ContractError> {
+    let Config { owner, .. } = config_read(deps.storage)?;
     if info.sender != owner {
         return Err(ContractError::Unauthorized {});
     }
-
-    // validate executor list for client verification
-    for executor in executors {
-        deps.api.addr_validate(&executor)?;
+    let request = requests().load(deps.storage, stage)?;
+    if stored_request_status(&request) != RequestStatus::Pending {
+        return Err(ContractError::AlreadyFinished {});
     }
 
-    // check merkle root length
-    let mut root_buf: [u8; 32] = [0; 32];
-    hex::decode_to_slice(mroot.to_string(), &mut root_buf)?;
+    let response = refund_request(deps, stage, request, RequestStatus::Cancelled)?;
+    Ok(response.add_attributes(vec![
+        attr("action", "cancel_request"),
+        attr("stage", stage.to_string()),
+    ]))
+}
 
-    let Request { merkle_root, .. } = requests().load(deps.storage, stage)?;
-    if merkle_root.ne("") {
-        return Err(ContractError::AlreadyFinished {});
+pub fn handle_rotate_executor_key(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    pubkey: Binary,
+    signature: Binary,
+) -> Result<Response, ContractError> {
+    let executor = deps.api.addr_canonicalize(info.sender.as_str())?;
+    let mut executor_info =
+        read_executor(deps.storage, &executor).ok_or(ContractError::Unauthorized {})?;
+    if pubkey.len() != 33 {
+        return Err(ContractError::WrongLength {});
     }
+    // the new key signs the rotation so nobody can claim a key they do not hold
+    let message_hash = executor_key_signing_hash(&env.contract.address, &info.sender, &pubkey);
+    verify_signature(deps.api, &message_hash, &signature, &pubkey)?;
 
-    // if merkle root empty then update new
-    requests().update(deps.storage, stage, |request| {
-        if let Some(mut request) = request {
-            request.merkle_root = mroot.clone();
-            request.submit_merkle_height = env.block.height;
-            {
-                return Ok(request);
-            }
-        }
-        Err(StdError::generic_err("Invalid request empty"))
-    })?;
+    executor_info.pubkey = Some(pubkey.clone());
+    store_executor(deps.storage, &executor, &executor_info)?;
 
     Ok(Response::new().add_attributes(vec![
-        attr("action", "register_merkle_root"),
-        attr("current_stage", stage.to_string()),
-        attr("merkle_root", mroot),
+        attr("action", "rotate_executor_key"),
+        attr("executor", info.sender),
+        attr("pubkey", pubkey.to_base64()),
     ]))
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::Config {} => to_binary(&query_config(deps)?),
-        QueryMsg::GetExecutors {
-            start,
-            end,
-            order,
-            limit,
-        } => to_binary(&query_executors(deps, start, end, order, limit)?),
-        QueryMsg::CheckExecutorInList { address } => {
-            let result = read_executor(deps.storage, deps.api.addr_canonicalize(&address)?);
-            to_binary(&result.is_some())
-        }
-        QueryMsg::GetExecutorSize {} => to_binary(&query_executor_size(deps)),
-        QueryMsg::GetRequest { stage } => to_binary(&query_request(deps, stage)?),
-        QueryMsg::GetRequests {
-            offset,
-            limit,
-            order,
-        } => to_binary(&query_requests(deps, offset, limit, order)?),
-        QueryMsg::GetRequestsByService {
-            service,
-            offset,
-            limit,
-            order,
-        } => to_binary(&query_requests_by_service(
-            deps, service, offset, limit, order,
-        )?),
-        QueryMsg::GetRequestsByMerkleRoot {
-            merkle_root,
-            offset,
-            limit,
-            order,
-        } => to_binary(&query_requests_by_merkle_root(
-            deps,
-            merkle_root,
-            offset,
-            limit,
-            order,
-        )?),
-        QueryMsg::LatestStage {} => to_binary(&query_latest_stage(deps)?),
-        QueryMsg::VerifyData { stage, data, proof } => {
-            to_binary(&verify_data(deps, stage, data, proof)?)
-        }
-        QueryMsg::GetService { service_name } => {
-            let service = read_service_info(deps.storage, service_name.as_bytes())?;
-            to_binary(&service)
-        }
-        QueryMsg::GetServices {
-            start,
-            end,
-            order,
-            limit,
-        } => to_binary(&query_services(deps, start, end, order, limit)?),
-    }
+pub fn handle_update_executor_metadata(
+    deps: DepsMut,
+    info: MessageInfo,
+    metadata: Option<String>,
+) -> Result<Response, ContractError> {
+    let executor = deps.api.addr_canonicalize(info.sender.as_str())?;
+    let mut executor_info =
+        read_executor(deps.storage, &executor).ok_or(ContractError::Unauthorized {})?;
+    executor_info.metadata = metadata;
+    store_executor(deps.storage, &executor, &executor_info)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "update_executor_metadata"),
+        attr("executor", info.sender),
+    ]))
 }
 
-pub fn verify_data(
-    deps: Deps,
-    stage: u64,
-    data: Binary,
-    proofs: Option<Vec<String>>,
-) -> StdResult<bool> {
-    let Request { merkle_root, .. } = requests().load(deps.storage, stage)?;
-    if merkle_root.is_empty() {
-        return Err(StdError::generic_err(
-            "No merkle root found for this request",
-        ));
+pub fn handle_apply_as_executor(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    pubkey: Binary,
+    signature: Binary,
+    metadata: Option<String>,
+) -> Result<Response, ContractError> {
+    let applicant = deps.api.addr_canonicalize(info.sender.as_str())?;
+    if read_executor(deps.storage, &applicant).is_some()
+        || read_executor_application(deps.storage, &applicant)
+            .is_some_and(|application| application.approved)
+    {
+        return Err(ContractError::AlreadyExecutor {});
     }
-    let mut final_proofs: Vec<String> = vec![];
-    if let Some(proofs) = proofs {
-        final_proofs = proofs;
+    if pubkey.len() != 33 {
+        return Err(ContractError::WrongLength {});
     }
+    let message_hash = executor_key_signing_hash(&env.contract.address, &info.sender, &pubkey);
+    verify_signature(deps.api, &message_hash, &signature, &pubkey)?;
 
-    let hash = sha2::Sha256::digest(data.as_slice())
-        .as_slice()
-        .try_into()
-        .map_err(|_| StdError::generic_err("wrong length"))?;
+    store_executor_application(
+        deps.storage,
+        &applicant,
+        &ExecutorApplication {
+            pubkey,
+            metadata,
+            height: env.block.height,
+            approved: false,
+        },
+    )?;
 
-    let hash = final_proofs.into_iter().try_fold(hash, |hash, p| {
-        let mut proof_buf = [0; 32];
-        hex::decode_to_slice(p, &mut proof_buf)
-            .map_err(|_| StdError::generic_err("error decoding"))?;
-        let mut hashes = [hash, proof_buf];
-        hashes.sort_unstable();
-        sha2::Sha256::digest(&hashes.concat())
-            .as_slice()
-            .try_into()
-            .map_err(|_| StdError::generic_err("wrong length"))
-    })?;
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "apply_as_executor"),
+        attr("applicant", info.sender),
+    ]))
+}
 
-    let mut root_buf: [u8; 32] = [0; 32];
-    hex::decode_to_slice(merkle_root, &mut root_buf)
-        .map_err(|_| StdError::generic_err("error decoding"))?;
-    let mut verified = false;
-    if root_buf == hash {
-        verified = true;
+pub fn handle_approve_executor(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    applicant: String,
+) -> Result<Response, ContractError> {
+    if info.sender != config_read(deps.storage)?.owner {
+        return Err(ContractError::Unauthorized {});
     }
-    Ok(verified)
-}
+    let applicant_canonical = deps.api.addr_canonicalize(&applicant)?;
+    if read_executor(deps.storage, &applicant_canonical).is_some() {
+        return Err(ContractError::AlreadyExecutor {});
+    }
+    let mut application = read_executor_application(deps.storage, &applicant_canonical)
+        .ok_or(ContractError::ApplicationNotFound {})?;
+    // approved applicants are already queued to join
+    if application.approved {
+        return Err(ContractError::InvalidInput {});
+    }
+    application.approved = true;
+    store_executor_application(deps.storage, &applicant_canonical, &application)?;
+    queue_executor_changes(
+        deps.storage,
+        vec![applicant_canonical],
+        vec![],
+        env.block.height,
+    )?;
 
-pub fn query_config(deps: Deps) -> StdResult<Config> {
-    config_read(deps.storage)
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "approve_executor"),
+        attr("applicant", applicant),
+    ]))
 }
 
-// ============================== Query Handlers ==============================
+pub fn handle_reject_executor(
+    deps: DepsMut,
+    info: MessageInfo,
+    applicant: String,
+) -> Result<Response, ContractError> {
+    if info.sender != config_read(deps.storage)?.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let applicant_canonical = deps.api.addr_canonicalize(&applicant)?;
+    let application = read_executor_application(deps.storage, &applicant_canonical)
+        .ok_or(ContractError::ApplicationNotFound {})?;
+    // approved applicants are already queued to join, the owner removes them as executors
+    if application.approved {
+        return Err(ContractError::InvalidInput {});
+    }
+    remove_executor_application(deps.storage, &applicant_canonical);
 
-pub fn query_request(deps: Deps, stage: u64) -> StdResult<Request> {
-    let request = requests().load(deps.storage, stage)?;
-    Ok(request)
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "reject_executor"),
+        attr("applicant", applicant),
+    ]))
 }
 
-fn parse_request<'a>(item: StdResult<(u64, Request)>) -> StdResult<RequestResponse> {
-    item.and_then(|(id, request)| {
-        // will panic if length is greater than 8, but we can make sure it is u64
-        Ok(RequestResponse {
-            stage: id,
-            requester: request.requester,
-            request_height: request.request_height,
-            submit_merkle_height: request.submit_merkle_height,
-            merkle_root: request.merkle_root,
-            threshold: request.threshold,
-            service: request.service,
-            input: request.input,
+pub fn handle_bond_executor(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let Config {
+        min_executor_bond, ..
+    } = config_read(deps.storage)?;
+    let bond_denom = min_executor_bond
+        .ok_or(ContractError::BondingDisabled {})?
+        .denom;
+    let executor = deps.api.addr_canonicalize(info.sender.as_str())?;
+    if read_executor(deps.storage, &executor).is_none() {
+        return Err(ContractError::Unauthorized {});
+    }
+    let amount = match info.funds.as_slice() {
+        [fund] if fund.denom == bond_denom && !fund.amount.is_zero() => fund.amount,
+        _ => return Err(ContractError::InvalidInput {}),
+    };
+
+    let mut bond = read_executor_bond(deps.storage, &executor);
+    if !bond.is_empty() && bond.denom != bond_denom {
+        return Err(ContractError::InvalidInput {});
+    }
+    bond.denom = bond_denom;
+    bond.amount += amount;
+    store_executor_bond(deps.storage, &executor, &bond)?;
+    update_executor_status(deps.storage, &executor)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "bond_executor"),
+        attr("executor", info.sender),
+        attr("amount", amount),
+        attr("bonded", bond.amount),
+    ]))
+}
+
+pub fn handle_unbond_executor(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let Config {
+        unbonding_period, ..
+    } = config_read(deps.storage)?;
+    let executor = deps.api.addr_canonicalize(info.sender.as_str())?;
+    // the bond answers for the roots under challenge until they are resolved
+    if open_challenge_count_read(deps.storage, &executor) > 0 {
+        return Err(ContractError::OpenChallenges {});
+    }
+    let mut bond = read_executor_bond(deps.storage, &executor);
+    if amount.is_zero() {
+        return Err(ContractError::InvalidInput {});
+    }
+    bond.amount = bond
+        .amount
+        .checked_sub(amount)
+        .map_err(|_| ContractError::InsufficientFunds {})?;
+    bond.unbonding.push(Unbonding {
+        denom: bond.denom.clone(),
+        amount,
+        release_height: env.block.height.saturating_add(unbonding_period),
+    });
+    store_executor_bond(deps.storage, &executor, &bond)?;
+    update_executor_status(deps.storage, &executor)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "unbond_executor"),
+        attr("executor", info.sender),
+        attr("amount", amount),
+        attr("bonded", bond.amount),
+    ]))
+}
+
+pub fn handle_withdraw_unbonded(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let executor = deps.api.addr_canonicalize(info.sender.as_str())?;
+    // the funds may still be slashed for a root under challenge
+    if open_challenge_count_read(deps.storage, &executor) > 0 {
+        return Err(ContractError::OpenChallenges {});
+    }
+    let mut bond = read_executor_bond(deps.storage, &executor);
+    let (released, unbonding): (Vec<Unbonding>, Vec<Unbonding>) = bond
+        .unbonding
+        .into_iter()
+        .partition(|unbonding| unbonding.release_height <= env.block.height);
+    // unbonded funds go back in the denom they were bonded in
+    let mut amount: Vec<Coin> = vec![];
+    for unbonding in released {
+        add_coin(&mut amount, &unbonding.denom, unbonding.amount);
+    }
+    if amount.is_empty() {
+        return Err(ContractError::NothingToWithdraw {});
+    }
+    bond.unbonding = unbonding;
+    store_executor_bond(deps.storage, &executor, &bond)?;
+
+    let withdrawn = amount
+        .iter()
+        .map(Coin::to_string)
+        .collect::<Vec<String>>()
+        .join(",");
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount,
         })
-    })
+        .add_attributes(vec![
+            attr("action", "withdraw_unbonded"),
+            attr("executor", info.sender),
+            attr("amount", withdrawn),
+        ]))
 }
 
-pub fn query_requests(
-    deps: Deps,
-    offset: Option<u64>,
-    limit: Option<u8>,
-    order: Option<u8>,
-) -> StdResult<Vec<RequestResponse>> {
-    let (limit, min, max, order_enum) = get_range_params(offset, limit, order);
-    let requests: StdResult<Vec<RequestResponse>> = requests()
-        .range(deps.storage, min, max, order_enum)
-        .take(limit)
-        .map(|kv_item| parse_request(kv_item))
-        .collect();
-    Ok(requests?)
+pub fn handle_unjail_executor(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let executor = deps.api.addr_canonicalize(info.sender.as_str())?;
+    let mut executor_info =
+        read_executor(deps.storage, &executor).ok_or(ContractError::Unauthorized {})?;
+    let jailed_until = executor_info
+        .jailed_until
+        .ok_or(ContractError::NotJailed {})?;
+    if env.block.height < jailed_until {
+        return Err(ContractError::StillJailed {});
+    }
+    executor_info.jailed_until = None;
+    store_executor(deps.storage, &executor, &executor_info)?;
+    update_executor_status(deps.storage, &executor)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "unjail_executor"),
+        attr("executor", info.sender),
+    ]))
 }
 
-pub fn query_requests_by_service(
-    deps: Deps,
-    service: String,
-    offset: Option<u64>,
-    limit: Option<u8>,
-    order: Option<u8>,
-) -> StdResult<Vec<RequestResponse>> {
-    let (limit, min, max, order_enum) = get_range_params(offset, limit, order);
-    let request_responses: StdResult<Vec<RequestResponse>> = requests()
-        .idx
-        .service
-        .prefix(service.as_bytes().to_vec())
-        .range(deps.storage, min, max, order_enum)
-        .take(limit)
-        .map(|kv_item| parse_request(kv_item))
-        .collect();
-    Ok(request_responses?)
+pub fn handle_claim_reward(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let executor = deps.api.addr_canonicalize(info.sender.as_str())?;
+    let rewards = read_executor_rewards(deps.storage, &executor);
+    if rewards.is_empty() {
+        return Err(ContractError::NoReward {});
+    }
+    remove_executor_rewards(deps.storage, &executor);
+
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: rewards,
+        })
+        .add_attributes(vec![
+            attr("action", "claim_reward"),
+            attr("executor", info.sender),
+        ]))
 }
 
-pub fn query_requests_by_merkle_root(
-    deps: Deps,
+/// Sums the service base fee and the fee of each data source, grouped by denom.
+pub fn get_service_fees(service: &Service) -> StdResult<Vec<Coin>> {
+    let mut fees: Vec<Coin> = vec![];
+    let dsource_fees = service
+        .dsources
+        .iter()
+        .filter_map(|dsource| dsource.fee.as_ref());
+    for fee in service.fee.iter().chain(dsource_fees) {
+        if fee.amount.is_zero() {
+            continue;
+        }
+        match fees.iter_mut().find(|coin| coin.denom == fee.denom) {
+            Some(coin) => coin.amount = coin.amount.checked_add(fee.amount)?,
+            None => fees.push(fee.clone()),
+        }
+    }
+    Ok(fees)
+}
+
+/// Checks that the funds cover the fees and returns what is left of the funds.
+fn deduct_fees(fees: &[Coin], funds: &[Coin]) -> Result<Vec<Coin>, ContractError> {
+    let mut remaining = funds.to_vec();
+    for fee in fees {
+        let paid = remaining
+            .iter_mut()
+            .find(|coin| coin.denom == fee.denom)
+            .ok_or(ContractError::InsufficientFunds {})?;
+        paid.amount = paid
+            .amount
+            .checked_sub(fee.amount)
+            .map_err(|_| ContractError::InsufficientFunds {})?;
+    }
+    remaining.retain(|coin| !coin.amount.is_zero());
+    Ok(remaining)
+}
+
+pub fn execute_register_merkle_root(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    stage: u64,
+    mroot: String,
+    executors: Vec<String>,
+) -> Result<Response, ContractError> {
+    let Config {
+        owner,
+        owner_registration,
+        ..
+    } = config_read(deps.storage)?;
+
+    // merkle roots go through executor signatures unless the owner is trusted with them
+    if !owner_registration || info.sender != owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // validate executor list for client verification, each executor is rewarded once
+    let mut executor_addrs: Vec<Addr> = vec![];
+    for executor in executors {
+        let executor = deps.api.addr_validate(&executor)?;
+        if !executor_addrs.contains(&executor) {
+            executor_addrs.push(executor);
+        }
+    }
+
+    // check merkle root length
+    let mut root_buf: [u8; 32] = [0; 32];
+    hex::decode_to_slice(&mroot, &mut root_buf)?;
+
+    let mut request = requests().load(deps.storage, stage)?;
+    assert_request_pending(&request, &env)?;
+    // escrowed fees can only be released to the executors who served the request
+    if executor_addrs.is_empty() && !request.fees.is_empty() {
+        return Err(ContractError::InvalidInput {});
+    }
+    for executor in &executor_addrs {
+        let executor_canonical = deps.api.addr_canonicalize(executor.as_str())?;
+        if !is_request_executor(deps.storage, &request, executor, &executor_canonical) {
+            return Err(ContractError::Unauthorized {});
+        }
+    }
+
+    let callback = register_merkle_root(
+        deps,
+        &env,
+        &mut request,
+        MerkleRootSubmission {
+            stage,
+            merkle_root: mroot.clone(),
+            executors: executor_addrs,
+        },
+    )?;
+
+    Ok(Response::new()
+        .add_submessages(callback)
+        .add_attributes(vec![
+            attr("action", "register_merkle_root"),
+            attr("current_stage", stage.to_string()),
+            attr("merkle_root", mroot),
+            attr("status", request.status.as_str()),
+        ]))
+}
+
+pub fn execute_submit_merkle_root(
+    deps: DepsMut,
+    env: Env,
+    stage: u64,
+    mroot: String,
+    signatures: Vec<ExecutorSignature>,
+) -> Result<Response, ContractError> {
+    let mut root_buf: [u8; 32] = [0; 32];
+    hex::decode_to_slice(&mroot, &mut root_buf)?;
+
+    let mut request = requests().load(deps.storage, stage)?;
+    assert_request_pending(&request, &env)?;
+
+    let message_hash = merkle_root_signing_hash(&env.contract.address, stage, &root_buf);
+    // signers jailed or removed since they signed no longer count
+    let mut signers = vec![];
+    for signer in read_merkle_root_signers(deps.storage, stage, &root_buf) {
+        let signer_canonical = deps.api.addr_canonicalize(signer.as_str())?;
+        if read_executor(deps.storage, &signer_canonical).is_some()
+            && is_request_executor(deps.storage, &request, &signer, &signer_canonical)
+        {
+            signers.push(signer);
+        }
+    }
+    for ExecutorSignature {
+        executor,
+        pubkey,
+        signature,
+    } in signatures
+    {
+        let executor = deps.api.addr_validate(&executor)?;
+        if signers.contains(&executor) {
+            continue;
+        }
+        let executor_canonical = deps.api.addr_canonicalize(executor.as_str())?;
+        let executor_info = read_executor(deps.storage, &executor_canonical)
+            .filter(|_| is_request_executor(deps.storage, &request, &executor, &executor_canonical))
+            .ok_or(ContractError::Unauthorized {})?;
+        verify_executor_signature(
+            deps.api,
+            &executor_canonical,
+            &executor_info,
+            &message_hash,
+            &signature,
+            pubkey.as_ref(),
+        )?;
+        signers.push(executor);
+    }
+
+    // the root is registered once enough executors agree on it
+    if (signers.len() as u64) < request.threshold.max(1) {
+        store_merkle_root_signers(deps.storage, stage, &root_buf, &signers)?;
+        return Ok(Response::new().add_attributes(vec![
+            attr("action", "submit_merkle_root"),
+            attr("stage", stage.to_string()),
+            attr("merkle_root", mroot),
+            attr("signatures", signers.len().to_string()),
+        ]));
+    }
+
+    let signatures = signers.len();
+    let callback = register_merkle_root(
+        deps,
+        &env,
+        &mut request,
+        MerkleRootSubmission {
+            stage,
+            merkle_root: hex::encode(root_buf),
+            executors: signers,
+        },
+    )?;
+
+    Ok(Response::new()
+        .add_submessages(callback)
+        .add_attributes(vec![
+            attr("action", "submit_merkle_root"),
+            attr("stage", stage.to_string()),
+            attr("merkle_root", mroot),
+            attr("signatures", signatures.to_string()),
+            attr("status", request.status.as_str()),
+        ]))
+}
+
+/// Merkle root agreed on for a stage, along with the executors who served it.
+struct MerkleRootSubmission {
+    stage: u64,
     merkle_root: String,
-    offset: Option<u64>,
-    limit: Option<u8>,
-    order: Option<u8>,
-) -> StdResult<Vec<RequestResponse>> {
-    let (limit, min, max, order_enum) = get_range_params(offset, limit, order);
-    let request_responses: StdResult<Vec<RequestResponse>> = requests()
-        .idx
-        .merkle_root
-        .prefix(merkle_root.as_bytes().to_vec())
-        .range(deps.storage, min, max, order_enum)
-        .take(limit)
-        .map(|kv_item| parse_request(kv_item))
-        .collect();
-    Ok(request_responses?)
+    executors: Vec<Addr>,
 }
 
-pub fn query_latest_stage(deps: Deps) -> StdResult<LatestStageResponse> {
-    let latest_stage = latest_stage_read(deps.storage)?;
-    let resp = LatestStageResponse { latest_stage };
+/// Stores the merkle root on a pending request, without challenge period the root is final right away.
+/// Returns the callback of the request if it got finalized.
+fn register_merkle_root(
+    deps: DepsMut,
+    env: &Env,
+    request: &mut Request,
+    submission: MerkleRootSubmission,
+) -> StdResult<Option<SubMsg>> {
+    let MerkleRootSubmission {
+        stage,
+        merkle_root,
+        executors,
+    } = submission;
+    let Config {
+        challenge_period, ..
+    } = config_read(deps.storage)?;
+    request.merkle_root = merkle_root;
+    request.submit_merkle_height = env.block.height;
+    request.challenge_end_height = Some(env.block.height.saturating_add(challenge_period));
+    request.executors = executors;
+    request.status = RequestStatus::Submitted;
+    // signatures collected for competing roots are of no use anymore
+    remove_merkle_root_signers(deps.storage, stage);
+    let mut callback = None;
+    if challenge_period == 0 {
+        callback = finalize_request(deps.storage, deps.api, stage, request)?;
+    }
+    requests().save(deps.storage, stage, request)?;
+    Ok(callback)
+}
 
-    Ok(resp)
+/// Marks the request as finalized and pays the executors who served it. Returns the message
+/// notifying the callback contract of the request, if any.
+fn finalize_request(
+    storage: &mut dyn Storage,
+    api: &dyn Api,
+    stage: u64,
+    request: &mut Request,
+) -> StdResult<Option<SubMsg>> {
+    let executors = request
+        .executors
+        .iter()
+        .map(|executor| api.addr_canonicalize(executor.as_str()))
+        .collect::<StdResult<Vec<CanonicalAddr>>>()?;
+    distribute_rewards(storage, &executors, &request.fees)?;
+    request.status = RequestStatus::Finalized;
+    request
+        .callback
+        .as_ref()
+        .map(|callback| callback_msg(stage, request, callback))
+        .transpose()
 }
 
-pub fn query_services(
-    deps: Deps,
-    start: Option<String>,
-    end: Option<String>,
-    order: Option<u8>,
-    limit: Option<u8>,
-) -> StdResult<Vec<ServiceInfoResponse>> {
-    read_service_infos(
+/// Calls back the requesting contract, a failing callback is only reported in `reply` so it
+/// cannot block the finalization. The gas limit keeps a callback running out of gas from
+/// failing the whole transaction.
+fn callback_msg(stage: u64, request: &Request, callback: &Callback) -> StdResult<SubMsg> {
+    let msg = WasmMsg::Execute {
+        contract_addr: callback.contract.to_string(),
+        msg: to_binary(&CallbackMsg::AioracleCallback {
+            stage,
+            merkle_root: request.merkle_root.clone(),
+            msg: callback.msg.clone(),
+        })?,
+        funds: vec![],
+    };
+    Ok(SubMsg::reply_on_error(msg, stage).with_gas_limit(CALLBACK_GAS_LIMIT))
+}
+
+/// Whether the merkle root of a submitted request can still be challenged at the given height.
+fn is_challengeable(request: &Request, height: u64) -> bool {
+    request.status == RequestStatus::Submitted
+        && request
+            .challenge_end_height
+            .is_some_and(|challenge_end_height| height < challenge_end_height)
+}
+
+/// Whether the merkle root of the request is final at the given height, a submitted root is
+/// final once its challenge period is over, even if not finalized yet.
+fn is_request_final(request: &Request, height: u64) -> bool {
+    stored_request_status(request) == RequestStatus::Finalized
+        || (request.status == RequestStatus::Submitted && !is_challengeable(request, height))
+}
+
+pub fn handle_finalize_merkle_root(
+    deps: DepsMut,
+    env: Env,
+    stage: u64,
+) -> Result<Response, ContractError> {
+    let mut request = requests().load(deps.storage, stage)?;
+    if request.status != RequestStatus::Submitted {
+        return Err(ContractError::InvalidRequestStatus {});
+    }
+    if is_challengeable(&request, env.block.height) {
+        return Err(ContractError::ChallengePeriodNotEnded {});
+    }
+
+    let callback = finalize_request(deps.storage, deps.api, stage, &mut request)?;
+    requests().save(deps.storage, stage, &request)?;
+
+    Ok(Response::new()
+        .add_submessages(callback)
+        .add_attributes(vec![
+            attr("action", "finalize_merkle_root"),
+            attr("stage", stage.to_string()),
+        ]))
+}
+
+pub fn handle_challenge_merkle_root(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    stage: u64,
+    evidence: String,
+) -> Result<Response, ContractError> {
+    let Config {
+        challenge_deposit, ..
+    } = config_read(deps.storage)?;
+    let challenger = deps.api.addr_canonicalize(info.sender.as_str())?;
+    if !read_executor(deps.storage, &challenger).is_some_and(|executor| executor.is_active) {
+        return Err(ContractError::Unauthorized {});
+    }
+    let mut request = requests().load(deps.storage, stage)?;
+    if !is_challengeable(&request, env.block.height) {
+        return Err(ContractError::ChallengePeriodEnded {});
+    }
+    // the deposit stays in the contract until the challenge is resolved
+    let deposit: Vec<Coin> = challenge_deposit
+        .into_iter()
+        .filter(|deposit| !deposit.amount.is_zero())
+        .collect();
+    let surplus = deduct_fees(&deposit, &info.funds)?;
+
+    request.status = RequestStatus::Challenged;
+    requests().save(deps.storage, stage, &request)?;
+    update_open_challenges(deps.storage, deps.api, &request.executors, true)?;
+    store_challenge(
         deps.storage,
-        start.map(|start| String::into_bytes(start)).as_deref(),
-        end.map(|end| String::into_bytes(end)).as_deref(),
-        order,
-        limit,
-    )
+        stage,
+        &Challenge {
+            challenger: info.sender.clone(),
+            merkle_root: request.merkle_root,
+            evidence,
+            height: env.block.height,
+            deposit,
+            upheld: None,
+        },
+    )?;
+
+    let mut response = Response::new();
+    if !surplus.is_empty() {
+        response = response.add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: surplus,
+        });
+    }
+    Ok(response.add_attributes(vec![
+        attr("action", "challenge_merkle_root"),
+        attr("stage", stage.to_string()),
+        attr("challenger", info.sender),
+    ]))
+}
+
+pub fn handle_resolve_challenge(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    stage: u64,
+    upheld: bool,
+) -> Result<Response, ContractError> {
+    let Config {
+        owner,
+        burn_slashed,
+        ..
+    } = config_read(deps.storage)?;
+    if info.sender != owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let mut request = requests().load(deps.storage, stage)?;
+    if request.status != RequestStatus::Challenged {
+        return Err(ContractError::InvalidRequestStatus {});
+    }
+    let mut challenge = read_challenge(deps.storage, stage)?;
+    challenge.upheld = Some(upheld);
+    let deposit = std::mem::take(&mut challenge.deposit);
+    store_challenge(deps.storage, stage, &challenge)?;
+    update_open_challenges(deps.storage, deps.api, &request.executors, false)?;
+
+    let mut response = Response::new();
+    if upheld {
+        if !deposit.is_empty() {
+            response = response.add_message(BankMsg::Send {
+                to_address: challenge.challenger.to_string(),
+                amount: deposit,
+            });
+        }
+
+        // the executors who registered the wrong root lose part of their bond
+        let (slashed, events) =
+            slash_executors(deps.storage, deps.api, &request.executors, env.block.height)?;
+        response = response.add_events(events);
+        if !slashed.is_empty() {
+            if burn_slashed {
+                response = response.add_message(BankMsg::Burn { amount: slashed });
+            } else {
+                let challenger = deps.api.addr_canonicalize(challenge.challenger.as_str())?;
+                distribute_rewards(deps.storage, &[challenger], &slashed)?;
+            }
+        }
+
+        // the root is wrong, the request waits for a new one
+        remove_request_results(deps.storage, stage);
+        request.merkle_root = String::from("");
+        request.submit_merkle_height = 0u64;
+        request.challenge_end_height = None;
+        request.executors = vec![];
+        request.status = RequestStatus::Pending;
+    } else {
+        // the deposit of a rejected challenge goes to the executors it held up, if any
+        let executors = request
+            .executors
+            .iter()
+            .map(|executor| deps.api.addr_canonicalize(executor.as_str()))
+            .collect::<StdResult<Vec<CanonicalAddr>>>()?;
+        if executors.is_empty() && !deposit.is_empty() {
+            response = response.add_message(BankMsg::Burn { amount: deposit });
+        } else {
+            distribute_rewards(deps.storage, &executors, &deposit)?;
+        }
+        let callback = finalize_request(deps.storage, deps.api, stage, &mut request)?;
+        response = response.add_submessages(callback);
+    }
+    requests().save(deps.storage, stage, &request)?;
+
+    Ok(response.add_attributes(vec![
+        attr("action", "resolve_challenge"),
+        attr("stage", stage.to_string()),
+        attr("upheld", upheld.to_string()),
+    ]))
+}
+
+/// Stores a result proven against the registered merkle root so contracts can read it
+/// without carrying proofs around.
+pub fn handle_submit_result(
+    deps: DepsMut,
+    info: MessageInfo,
+    stage: u64,
+    data: Binary,
+    proof: Option<Vec<String>>,
+) -> Result<Response, ContractError> {
+    let request = requests().load(deps.storage, stage)?;
+    if info.sender != request.requester && !request.executors.contains(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    if !matches!(
+        stored_request_status(&request),
+        RequestStatus::Submitted | RequestStatus::Finalized
+    ) {
+        return Err(ContractError::InvalidRequestStatus {});
+    }
+    if data.len() > MAX_RESULT_SIZE {
+        return Err(ContractError::ResultTooLarge {
+            max_size: MAX_RESULT_SIZE,
+        });
+    }
+    let mut results = read_request_results(deps.storage, stage);
+    if results.contains(&data) {
+        return Err(ContractError::AlreadySubmitted {});
+    }
+    if results.len() >= MAX_RESULTS {
+        return Err(ContractError::TooManyResults {
+            max_results: MAX_RESULTS,
+        });
+    }
+    if !verify_merkle_proof(&request, &data, proof.unwrap_or_default())? {
+        return Err(ContractError::VerificationFailed {});
+    }
+    results.push(data);
+    store_request_results(deps.storage, stage, &results)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "submit_result"),
+        attr("stage", stage.to_string()),
+        attr("results", results.len().to_string()),
+    ]))
+}
+
+/// Only failed callbacks are replied to, the reply id is the stage of the request.
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(_deps: DepsMut, _env: Env, msg: Reply) -> StdResult<Response> {
+    let error = msg.result.into_result().err().unwrap_or_default();
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "callback_failed"),
+        attr("stage", msg.id.to_string()),
+        attr("error", error),
+    ]))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Config {} => to_binary(&query_config(deps)?),
+        QueryMsg::GetExecutors {
+            start,
+            end,
+            start_after,
+            order,
+            limit,
+        } => to_binary(&query_executors(
+            deps,
+            env.block.height,
+            start,
+            end,
+            start_after,
+            order,
+            limit,
+        )?),
+        QueryMsg::GetExecutor { address } => {
+            to_binary(&query_executor(deps, env.block.height, address)?)
+        }
+        QueryMsg::CheckExecutorInList { address } => {
+            to_binary(&query_executor_in_list(deps, env.block.height, address)?)
+        }
+        QueryMsg::CheckExecutorInSet { address, version } => to_binary(&is_executor_in_set(
+            deps.storage,
+            &deps.api.addr_canonicalize(&address)?,
+            version,
+        )),
+        QueryMsg::GetExecutorSetVersion {} => to_binary(&executor_set_version_read(deps.storage)),
+        QueryMsg::GetExecutorSize {} => to_binary(&query_executor_size(deps, env.block.height)?),
+        QueryMsg::GetCurrentEpochExecutors { start_after, limit } => to_binary(
+            &query_epoch_executors(deps, env.block.height, false, start_after, limit)?,
+        ),
+        QueryMsg::GetNextEpochExecutors { start_after, limit } => to_binary(
+            &query_epoch_executors(deps, env.block.height, true, start_after, limit)?,
+        ),
+        QueryMsg::GetExecutorBond { executor } => to_binary(&query_executor_bond(deps, executor)?),
+        QueryMsg::GetExecutorApplication { applicant } => {
+            to_binary(&query_executor_application(deps, applicant)?)
+        }
+        QueryMsg::GetExecutorApplications { start_after, limit } => {
+            to_binary(&query_executor_applications(deps, start_after, limit)?)
+        }
+        QueryMsg::GetExecutorRewards { executor } => {
+            to_binary(&query_executor_rewards(deps, executor)?)
+        }
+        QueryMsg::GetRequest { stage } => to_binary(&query_request(deps, &env, stage)?),
+        QueryMsg::GetRequests {
+            offset,
+            limit,
+            order,
+        } => to_binary(&query_requests(deps, &env, offset, limit, order)?),
+        QueryMsg::GetRequestsByService {
+            service,
+            offset,
+            limit,
+            order,
+        } => to_binary(&query_requests_by_service(
+            deps, &env, service, offset, limit, order,
+        )?),
+        QueryMsg::GetRequestsByStatus {
+            status,
+            offset,
+            limit,
+            order,
+        } => to_binary(&query_requests_by_status(
+            deps, &env, status, offset, limit, order,
+        )?),
+        QueryMsg::GetRequestsByMerkleRoot {
+            merkle_root,
+            offset,
+            limit,
+            order,
+        } => to_binary(&query_requests_by_merkle_root(
+            deps,
+            &env,
+            merkle_root,
+            offset,
+            limit,
+            order,
+        )?),
+        QueryMsg::GetRequestsByRequester {
+            requester,
+            offset,
+            limit,
+            order,
+        } => to_binary(&query_requests_by_requester(
+            deps, &env, requester, offset, limit, order,
+        )?),
+        QueryMsg::GetRequestsCountByRequester { requester } => {
+            to_binary(&query_requests_count_by_requester(deps, requester)?)
+        }
+        QueryMsg::SearchRequests {
+            filter,
+            offset,
+            limit,
+            order,
+        } => to_binary(&query_search_requests(
+            deps, &env, filter, offset, limit, order,
+        )?),
+        QueryMsg::LatestStage {} => to_binary(&query_latest_stage(deps)?),
+        QueryMsg::GetMerkleRootSigners { stage, merkle_root } => {
+            to_binary(&query_merkle_root_signers(deps, stage, merkle_root)?)
+        }
+        QueryMsg::GetChallenge { stage } => to_binary(&read_challenge(deps.storage, stage)?),
+        QueryMsg::VerifyData { stage, data, proof } => {
+            to_binary(&verify_data(deps, env, stage, data, proof)?)
+        }
+        QueryMsg::VerifyDataMulti {
+            stage,
+            leaves,
+            proof,
+            proof_flags,
+        } => to_binary(&verify_data_multi(
+            deps,
+            env,
+            stage,
+            leaves,
+            proof,
+            proof_flags,
+        )?),
+        QueryMsg::VerifyDataBatch { items } => to_binary(&verify_data_batch(deps, env, items)?),
+        QueryMsg::GetRequestResult { stage } => to_binary(&query_request_result(deps, stage)?),
+        QueryMsg::GetService { service_name } => {
+            let service = read_service_info(deps.storage, service_name.as_bytes())?;
+            to_binary(&service)
+        }
+        QueryMsg::GetServiceFees { service_name } => {
+            to_binary(&query_service_fees(deps, service_name)?)
+        }
+        QueryMsg::GetServices {
+            start,
+            end,
+            start_after,
+            order,
+            limit,
+        } => to_binary(&query_services(
+            deps,
+            start,
+            end,
+            start_after,
+            order,
+            limit,
+        )?),
+    }
+}
+
+pub fn verify_data(
+    deps: Deps,
+    env: Env,
+    stage: u64,
+    data: Binary,
+    proofs: Option<Vec<String>>,
+) -> StdResult<VerifyDataResponse> {
+    verify_stage(deps, env, stage, |request| {
+        verify_merkle_proof(request, &data, proofs.unwrap_or_default())
+    })
+}
+
+/// Verifies several leaves of a stage at once with a multiproof, as built by
+/// `MerkleTree::multiproof`.
+pub fn verify_data_multi(
+    deps: Deps,
+    env: Env,
+    stage: u64,
+    leaves: Vec<Binary>,
+    proof: Vec<String>,
+    proof_flags: Vec<Option<bool>>,
+) -> StdResult<VerifyDataResponse> {
+    verify_stage(deps, env, stage, |request| {
+        verify_merkle_multiproof(request, &leaves, &proof, &proof_flags)
+    })
+}
+
+/// Runs the verification against the merkle root of the stage and reports how final the root is.
+fn verify_stage(
+    deps: Deps,
+    env: Env,
+    stage: u64,
+    verify: impl FnOnce(&Request) -> StdResult<bool>,
+) -> StdResult<VerifyDataResponse> {
+    let request = requests().load(deps.storage, stage)?;
+    if request.merkle_root.is_empty() {
+        return Err(StdError::generic_err(
+            "No merkle root found for this request",
+        ));
+    }
+    let challengeable = is_challengeable(&request, env.block.height);
+    let finalized = is_request_final(&request, env.block.height);
+
+    let verified = verify(&request)?;
+    Ok(VerifyDataResponse {
+        verified,
+        finalized,
+        challengeable,
+    })
+}
+
+pub fn verify_data_batch(
+    deps: Deps,
+    env: Env,
+    items: Vec<VerifyDataItem>,
+) -> StdResult<Vec<VerifyDataBatchResponse>> {
+    if items.len() > MAX_VERIFY_BATCH {
+        return Err(StdError::generic_err(format!(
+            "Batch exceeds {} items",
+            MAX_VERIFY_BATCH
+        )));
+    }
+    Ok(items
+        .into_iter()
+        .map(|VerifyDataItem { stage, data, proof }| {
+            match verify_data(deps, env.clone(), stage, data, proof) {
+                Ok(VerifyDataResponse { verified, .. }) => VerifyDataBatchResponse {
+                    stage,
+                    verified,
+                    error: None,
+                },
+                Err(err) => VerifyDataBatchResponse {
+                    stage,
+                    verified: false,
+                    error: Some(err.to_string()),
+                },
+            }
+        })
+        .collect())
+}
+
+/// Whether the data is a leaf of the request merkle root given its proof.
+fn verify_merkle_proof(request: &Request, data: &[u8], proofs: Vec<String>) -> StdResult<bool> {
+    let proof = proofs
+        .iter()
+        .map(|p| decode_hash(p))
+        .collect::<StdResult<Vec<Hash>>>()?;
+    Ok(verify_proof(
+        request.hash_algorithm,
+        request.tree_format,
+        &decode_hash(&request.merkle_root)?,
+        data,
+        &proof,
+    ))
+}
+
+fn verify_merkle_multiproof(
+    request: &Request,
+    leaves: &[Binary],
+    proof: &[String],
+    proof_flags: &[Option<bool>],
+) -> StdResult<bool> {
+    let proof = proof
+        .iter()
+        .map(|p| decode_hash(p))
+        .collect::<StdResult<Vec<Hash>>>()?;
+    verify_multiproof(
+        request.hash_algorithm,
+        request.tree_format,
+        &decode_hash(&request.merkle_root)?,
+        leaves,
+        &proof,
+        proof_flags,
+    )
+}
+
+pub fn query_request_result(deps: Deps, stage: u64) -> StdResult<RequestResultResponse> {
+    let request = requests().load(deps.storage, stage)?;
+    Ok(RequestResultResponse {
+        stage,
+        merkle_root: request.merkle_root,
+        results: read_request_results(deps.storage, stage),
+    })
+}
+
+pub fn query_config(deps: Deps) -> StdResult<Config> {
+    config_read(deps.storage)
+}
+
+// ============================== Query Handlers ==============================
+
+pub fn query_request(deps: Deps, env: &Env, stage: u64) -> StdResult<Request> {
+    let mut request = requests().load(deps.storage, stage)?;
+    request.status = request_status(&request, env);
+    Ok(request)
+}
+
+fn parse_request(env: &Env, item: StdResult<(u64, Request)>) -> StdResult<RequestResponse> {
+    item.map(|(id, request)| {
+        // will panic if length is greater than 8, but we can make sure it is u64
+        RequestResponse {
+            status: request_status(&request, env),
+            stage: id,
+            requester: request.requester,
+            request_height: request.request_height,
+            submit_merkle_height: request.submit_merkle_height,
+            merkle_root: request.merkle_root,
+            threshold: request.threshold,
+            service: request.service,
+            input: request.input,
+            fees: request.fees,
+            timeout_height: request.timeout_height,
+            executors: request.executors,
+            callback: request.callback,
+            hash_algorithm: request.hash_algorithm,
+            tree_format: request.tree_format,
+            executor_set_version: request.executor_set_version,
+            allowed_executors: request.allowed_executors,
+            challenge_end_height: request.challenge_end_height,
+        }
+    })
+}
+
+pub fn query_requests(
+    deps: Deps,
+    env: &Env,
+    offset: Option<u64>,
+    limit: Option<u8>,
+    order: Option<u8>,
+) -> StdResult<RequestListResponse> {
+    let (limit, min, max, order_enum) = get_range_params(offset, limit, order);
+    let items = requests()
+        .range(deps.storage, min, max, order_enum)
+        .take(limit)
+        .map(|item| parse_request(env, item))
+        .collect::<StdResult<Vec<RequestResponse>>>()?;
+    // stages are numbered from 1 without gaps
+    let total = latest_stage_read(deps.storage)?;
+    Ok(to_request_list(items, limit, Some(total)))
+}
+
+fn to_request_list(
+    items: Vec<RequestResponse>,
+    limit: usize,
+    total: Option<u64>,
+) -> RequestListResponse {
+    let next_start_after = items
+        .last()
+        .filter(|_| items.len() == limit)
+        .map(|request| request.stage);
+    RequestListResponse {
+        items,
+        next_start_after,
+        total,
+    }
+}
+
+pub fn query_requests_by_service(
+    deps: Deps,
+    env: &Env,
+    service: String,
+    offset: Option<u64>,
+    limit: Option<u8>,
+    order: Option<u8>,
+) -> StdResult<RequestListResponse> {
+    let (limit, min, max, order_enum) = get_range_params(offset, limit, order);
+    let items = requests()
+        .idx
+        .service
+        .prefix(service.as_bytes().to_vec())
+        .range(deps.storage, min, max, order_enum)
+        .take(limit)
+        .map(|item| parse_request(env, item))
+        .collect::<StdResult<Vec<RequestResponse>>>()?;
+    let total = service_request_count_read(deps.storage, &service);
+    Ok(to_request_list(items, limit, Some(total)))
+}
+
+pub fn query_requests_by_status(
+    deps: Deps,
+    env: &Env,
+    status: RequestStatus,
+    offset: Option<u64>,
+    limit: Option<u8>,
+    order: Option<u8>,
+) -> StdResult<RequestListResponse> {
+    let (limit, min, max, order_enum) = get_range_params(offset, limit, order);
+    let by_status = |status: &RequestStatus| {
+        requests()
+            .idx
+            .status
+            .prefix(status.as_str().as_bytes().to_vec())
+            .range(deps.storage, min.clone(), max.clone(), order_enum)
+    };
+    // requests stored before statuses are missing from the index until `MigrateRequests` went
+    // through them all, all stages are searched meanwhile. Timed out requests are still stored
+    // as pending until reclaimed.
+    let items: Box<dyn Iterator<Item = StdResult<(u64, Request)>>> =
+        if !are_requests_migrated(deps.storage)? {
+            Box::new(requests().range(deps.storage, min.clone(), max.clone(), order_enum))
+        } else if status == RequestStatus::Expired {
+            Box::new(merge_requests(
+                by_status(&status),
+                by_status(&RequestStatus::Pending),
+                order_enum,
+            ))
+        } else {
+            Box::new(by_status(&status))
+        };
+
+    let mut found = vec![];
+    let mut next_start_after = None;
+    for (scanned, item) in items.enumerate() {
+        let (stage, request) = item?;
+        if request_status(&request, env) == status {
+            found.push(parse_request(env, Ok((stage, request)))?);
+        }
+        if found.len() == limit || scanned + 1 == MAX_SEARCH_SCAN {
+            next_start_after = Some(stage);
+            break;
+        }
+    }
+    Ok(RequestListResponse {
+        items: found,
+        next_start_after,
+        total: None,
+    })
+}
+
+/// Merges two ranges of requests ordered by stage.
+fn merge_requests<'a>(
+    a: impl Iterator<Item = StdResult<(u64, Request)>> + 'a,
+    b: impl Iterator<Item = StdResult<(u64, Request)>> + 'a,
+    order: Order,
+) -> impl Iterator<Item = StdResult<(u64, Request)>> + 'a {
+    let mut a = a.peekable();
+    let mut b = b.peekable();
+    std::iter::from_fn(move || {
+        let take_a = match (a.peek(), b.peek()) {
+            (Some(Ok((stage_a, _))), Some(Ok((stage_b, _)))) => {
+                (stage_a < stage_b) == matches!(order, Order::Ascending)
+            }
+            (Some(Err(_)), _) | (Some(_), None) => true,
+            (_, Some(_)) => false,
+            (None, None) => return None,
+        };
+        if take_a {
+            a.next()
+        } else {
+            b.next()
+        }
+    })
+}
+
+pub fn query_requests_by_requester(
+    deps: Deps,
+    env: &Env,
+    requester: String,
+    offset: Option<u64>,
+    limit: Option<u8>,
+    order: Option<u8>,
+) -> StdResult<RequestListResponse> {
+    let requester = deps.api.addr_validate(&requester)?;
+    let (limit, min, max, order_enum) = get_range_params(offset, limit, order);
+    let items = requests()
+        .idx
+        .requester
+        .prefix(requester.as_bytes().to_vec())
+        .range(deps.storage, min, max, order_enum)
+        .take(limit)
+        .map(|item| parse_request(env, item))
+        .collect::<StdResult<Vec<RequestResponse>>>()?;
+    let total = requester_request_count_read(deps.storage, &requester);
+    Ok(to_request_list(items, limit, Some(total)))
+}
+
+pub fn query_requests_count_by_requester(deps: Deps, requester: String) -> StdResult<u64> {
+    let requester = deps.api.addr_validate(&requester)?;
+    Ok(requester_request_count_read(deps.storage, &requester))
+}
+
+pub fn query_search_requests(
+    deps: Deps,
+    env: &Env,
+    filter: RequestFilter,
+    offset: Option<u64>,
+    limit: Option<u8>,
+    order: Option<u8>,
+) -> StdResult<SearchRequestsResponse> {
+    let requester = filter
+        .requester
+        .as_ref()
+        .map(|requester| deps.api.addr_validate(requester))
+        .transpose()?;
+    let (limit, _, _, order_enum) = get_range_params::<u64>(None, limit, order);
+    // requests are made in increasing height, the height filters bound the stages to search
+    let mut first_stage = match filter.min_height {
+        Some(min_height) => first_stage_from_height(deps.storage, min_height)?,
+        None => 1,
+    };
+    let mut last_stage = match filter.max_height {
+        Some(max_height) => {
+            first_stage_from_height(deps.storage, max_height.saturating_add(1))?.saturating_sub(1)
+        }
+        None => latest_stage_read(deps.storage)?,
+    };
+    match (offset, order_enum) {
+        (Some(offset), Order::Ascending) => first_stage = first_stage.max(offset.saturating_add(1)),
+        (Some(offset), Order::Descending) => last_stage = last_stage.min(offset.saturating_sub(1)),
+        (None, _) => {}
+    }
+    if first_stage > last_stage {
+        return Ok(SearchRequestsResponse {
+            requests: vec![],
+            next_offset: None,
+        });
+    }
+    let min = Some(Bound::inclusive(first_stage));
+    let max = Some(Bound::inclusive(last_stage));
+    let status_index = are_requests_migrated(deps.storage)?;
+    // the most selective index given by the filter narrows the search
+    let items = if let Some(requester) = &requester {
+        requests()
+            .idx
+            .requester
+            .prefix(requester.as_bytes().to_vec())
+            .range(deps.storage, min, max, order_enum)
+    } else if let Some(service) = &filter.service {
+        requests()
+            .idx
+            .service
+            .prefix(service.as_bytes().to_vec())
+            .range(deps.storage, min, max, order_enum)
+    } else if let Some(status) = filter.status.as_ref().filter(|status| {
+        // timed out requests are stored as pending
+        status_index && **status != RequestStatus::Expired
+    }) {
+        requests()
+            .idx
+            .status
+            .prefix(status.as_str().as_bytes().to_vec())
+            .range(deps.storage, min, max, order_enum)
+    } else {
+        requests().range(deps.storage, min, max, order_enum)
+    };
+
+    let mut found = vec![];
+    let mut next_offset = None;
+    for (scanned, item) in items.enumerate() {
+        let (stage, request) = item?;
+        if is_request_matching(env, &filter, requester.as_ref(), &request) {
+            found.push(parse_request(env, Ok((stage, request)))?);
+        }
+        if found.len() == limit || scanned + 1 == MAX_SEARCH_SCAN {
+            next_offset = Some(stage);
+            break;
+        }
+    }
+    Ok(SearchRequestsResponse {
+        requests: found,
+        next_offset,
+    })
+}
+
+/// First stage requested at or after the height, past the latest stage if there is none.
+fn first_stage_from_height(storage: &dyn Storage, height: u64) -> StdResult<u64> {
+    // stages are numbered from 1 without gaps
+    let mut low = 1;
+    let mut high = latest_stage_read(storage)?.saturating_add(1);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if requests().load(storage, middle)?.request_height < height {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    Ok(low)
+}
+
+/// Whether the request matches the filter, the height bounds are left to the searched stages.
+fn is_request_matching(
+    env: &Env,
+    filter: &RequestFilter,
+    requester: Option<&Addr>,
+    request: &Request,
+) -> bool {
+    requester.is_none_or(|requester| *requester == request.requester)
+        && filter
+            .service
+            .as_ref()
+            .is_none_or(|service| &request.service == service)
+        && filter
+            .status
+            .as_ref()
+            .is_none_or(|status| request_status(request, env) == *status)
+        && filter
+            .finalized
+            .is_none_or(|finalized| is_request_final(request, env.block.height) == finalized)
+}
+
+pub fn query_requests_by_merkle_root(
+    deps: Deps,
+    env: &Env,
+    merkle_root: String,
+    offset: Option<u64>,
+    limit: Option<u8>,
+    order: Option<u8>,
+) -> StdResult<RequestListResponse> {
+    let (limit, min, max, order_enum) = get_range_params(offset, limit, order);
+    let items = requests()
+        .idx
+        .merkle_root
+        .prefix(merkle_root.as_bytes().to_vec())
+        .range(deps.storage, min, max, order_enum)
+        .take(limit)
+        .map(|item| parse_request(env, item))
+        .collect::<StdResult<Vec<RequestResponse>>>()?;
+    Ok(to_request_list(items, limit, None))
+}
+
+pub fn query_latest_stage(deps: Deps) -> StdResult<LatestStageResponse> {
+    let latest_stage = latest_stage_read(deps.storage)?;
+    let resp = LatestStageResponse { latest_stage };
+
+    Ok(resp)
+}
+
+pub fn query_merkle_root_signers(
+    deps: Deps,
+    stage: u64,
+    merkle_root: String,
+) -> StdResult<Vec<Addr>> {
+    let mut root_buf: [u8; 32] = [0; 32];
+    hex::decode_to_slice(merkle_root, &mut root_buf)
+        .map_err(|_| StdError::generic_err("error decoding"))?;
+    Ok(read_merkle_root_signers(deps.storage, stage, &root_buf))
+}
+
+pub fn query_service_fees(deps: Deps, service_name: String) -> StdResult<ServiceFeesResponse> {
+    let service_info = read_service_info(deps.storage, service_name.as_bytes())?;
+    Ok(ServiceFeesResponse {
+        service_name,
+        fees: get_service_fees(&service_info.service)?,
+    })
+}
+
+pub fn query_services(
+    deps: Deps,
+    start: Option<String>,
+    end: Option<String>,
+    start_after: Option<String>,
+    order: Option<u8>,
+    limit: Option<u8>,
+) -> StdResult<ServiceListResponse> {
+    let (start, end) = range_after(
+        start.map(String::into_bytes),
+        end.map(String::into_bytes),
+        start_after.map(String::into_bytes),
+        match_order(order),
+    );
+    let items = read_service_infos(deps.storage, start.as_deref(), end.as_deref(), order, limit)?;
+    let next_start_after = items
+        .last()
+        .filter(|_| items.len() == limit.unwrap_or(DEFAULT_LIMIT) as usize)
+        .map(|service| service.service_name.clone());
+    Ok(ServiceListResponse {
+        items,
+        next_start_after,
+        total: Some(service_count_read(deps.storage)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::MerkleTree;
+    use crate::msg::{EpochExecutorsResponse, ExecutorListResponse, ExecutorsResponse};
+    use crate::state::{
+        active_executor_count_read, executor_count_read, read_executor_changes, remove_executor,
+        DataSourceState, Executor, HashAlgorithm, KEY_ACTIVE_EXECUTOR_COUNT, KEY_CONFIG,
+        KEY_EXECUTOR_COUNT, KEY_SERVICE_COUNT, PREFIX_EXECUTOR,
+    };
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{coin, coins, from_binary, CosmosMsg, Event, ReplyOn, SubMsgResult};
+    use cosmwasm_storage::to_length_prefixed;
+    use k256::ecdsa::signature::Signer;
+    use k256::ecdsa::{Signature, SigningKey};
+    use sha2::Digest;
+
+    fn instantiate_msg(executors: &[&str]) -> InstantiateMsg {
+        InstantiateMsg {
+            owner: None,
+            executors: Some(
+                executors
+                    .iter()
+                    .map(|executor| executor.to_string())
+                    .collect(),
+            ),
+            request_timeout: None,
+            challenge_period: None,
+            challenge_deposit: None,
+            min_executor_bond: None,
+            unbonding_period: None,
+            slash_fraction: None,
+            jail_period: None,
+            burn_slashed: None,
+            owner_registration: Some(true),
+            epoch_length: None,
+        }
+    }
+
+    fn add_service(deps: DepsMut, service_name: &str, fee: Option<Coin>) {
+        let msg = ExecuteMsg::AddService(AddServiceMsg {
+            service_name: service_name.to_string(),
+            service: Service {
+                dsources: vec![],
+                tcases: vec![],
+                oscript_url: String::new(),
+                fee,
+                request_timeout: None,
+                hash_algorithm: HashAlgorithm::Sha256,
+                tree_format: None,
+            },
+        });
+        execute(deps, mock_env(), mock_info("owner", &[]), msg).unwrap();
+    }
+
+    fn request_msg(service: &str, threshold: u64) -> ExecuteMsg {
+        ExecuteMsg::Request {
+            service: service.to_string(),
+            input: None,
+            threshold,
+            callback: None,
+        }
+    }
+
+    #[test]
+    fn verify_data_with_generated_proof() {
+        let mut deps = mock_dependencies();
+        let msg = instantiate_msg(&["executor1", "executor2", "executor3"]);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+
+        let leaves: Vec<Vec<u8>> = (0..5)
+            .map(|i| format!("result {}", i).into_bytes())
+            .collect();
+        let mut stage = 0;
+        for hash_algorithm in [HashAlgorithm::Sha256, HashAlgorithm::Keccak256] {
+            for tree_format in [TreeFormat::Legacy, TreeFormat::V1] {
+                stage += 1;
+                let service_name = format!("service {}", stage);
+                let service = Service {
+                    dsources: vec![],
+                    tcases: vec![],
+                    oscript_url: String::new(),
+                    fee: None,
+                    request_timeout: None,
+                    hash_algorithm,
+                    tree_format: Some(tree_format),
+                };
+                let msgs = [
+                    ExecuteMsg::AddService(AddServiceMsg {
+                        service_name: service_name.clone(),
+                        service,
+                    }),
+                    ExecuteMsg::Request {
+                        service: service_name,
+                        input: None,
+                        threshold: 1,
+                        callback: None,
+                    },
+                ];
+                for msg in msgs {
+                    execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+                }
+
+                let tree = MerkleTree::new(hash_algorithm, tree_format, &leaves);
+                let msg = ExecuteMsg::RegisterMerkleRoot {
+                    stage,
+                    merkle_root: tree.hex_root().unwrap(),
+                    executors: vec!["executor1".to_string()],
+                };
+                execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+
+                for (index, leaf) in leaves.iter().enumerate() {
+                    let verify = |data: &[u8]| -> bool {
+                        let msg = QueryMsg::VerifyData {
+                            stage,
+                            data: Binary::from(data),
+                            proof: tree.hex_proof(index),
+                        };
+                        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+                        from_binary::<VerifyDataResponse>(&res).unwrap().verified
+                    };
+                    assert!(verify(leaf));
+                    assert!(!verify(b"forged result"));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn service_fees_add_up_per_denom() {
+        let mut deps = mock_dependencies();
+        let msg = instantiate_msg(&["executor1"]);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        let dsource = |fee: Coin| DataSourceState {
+            language: "node".to_string(),
+            script_url: String::new(),
+            parameters: vec![],
+            fee: Some(fee),
+        };
+        let add_service = |deps: DepsMut, service_name: &str, dsources: Vec<DataSourceState>| {
+            let msg = ExecuteMsg::AddService(AddServiceMsg {
+                service_name: service_name.to_string(),
+                service: Service {
+                    dsources,
+                    tcases: vec![],
+                    oscript_url: String::new(),
+                    fee: Some(coin(10, "orai")),
+                    request_timeout: None,
+                    hash_algorithm: HashAlgorithm::Sha256,
+                    tree_format: None,
+                },
+            });
+            execute(deps, mock_env(), mock_info("owner", &[]), msg).unwrap();
+        };
+        let dsources = vec![
+            dsource(coin(5, "orai")),
+            dsource(coin(0, "atom")),
+            dsource(coin(7, "atom")),
+        ];
+        add_service(deps.as_mut(), "service", dsources);
+        let res = query_service_fees(deps.as_ref(), "service".to_string()).unwrap();
+        assert_eq!(res.fees, vec![coin(15, "orai"), coin(7, "atom")]);
+
+        // fees adding up past the maximum amount are rejected, not wrapped
+        add_service(
+            deps.as_mut(),
+            "overflow",
+            vec![dsource(coin(u128::MAX, "orai"))],
+        );
+        query_service_fees(deps.as_ref(), "overflow".to_string()).unwrap_err();
+        let info = mock_info("requester", &[coin(u128::MAX, "orai")]);
+        let err = execute(deps.as_mut(), mock_env(), info, request_msg("overflow", 1));
+        assert!(matches!(err.unwrap_err(), ContractError::Std(_)));
+    }
+
+    #[test]
+    fn verify_data_batch_reports_each_item() {
+        let mut deps = mock_dependencies();
+        let msg = instantiate_msg(&["executor1", "executor2", "executor3"]);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        add_service(deps.as_mut(), "service", None);
+        for _ in 0..2 {
+            let msg = request_msg("service", 1);
+            execute(deps.as_mut(), mock_env(), mock_info("requester", &[]), msg).unwrap();
+        }
+        let leaves = [b"result 0", b"result 1", b"result 2"];
+        let tree = MerkleTree::new(HashAlgorithm::Sha256, TreeFormat::V1, &leaves);
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            stage: 1,
+            merkle_root: tree.hex_root().unwrap(),
+            executors: vec!["executor1".to_string()],
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+
+        let item = |stage: u64, data: &[u8], proof: Option<Vec<String>>| VerifyDataItem {
+            stage,
+            data: Binary::from(data),
+            proof,
+        };
+        let items = vec![
+            item(1, leaves[0], tree.hex_proof(0)),
+            item(1, b"forged result", tree.hex_proof(0)),
+            item(1, leaves[1], tree.hex_proof(1)),
+            item(1, leaves[2], Some(vec!["not hex".to_string()])),
+            item(2, leaves[0], tree.hex_proof(0)),
+            item(3, leaves[0], tree.hex_proof(0)),
+        ];
+        let msg = QueryMsg::VerifyDataBatch { items };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let res: Vec<VerifyDataBatchResponse> = from_binary(&res).unwrap();
+        let verified: Vec<(u64, bool, bool)> = res
+            .iter()
+            .map(|item| (item.stage, item.verified, item.error.is_some()))
+            .collect();
+        assert_eq!(
+            verified,
+            vec![
+                (1, true, false),
+                (1, false, false),
+                (1, true, false),
+                (1, false, true),
+                (2, false, true),
+                (3, false, true),
+            ]
+        );
+
+        let items = vec![item(1, leaves[0], tree.hex_proof(0)); MAX_VERIFY_BATCH + 1];
+        let msg = QueryMsg::VerifyDataBatch { items };
+        query(deps.as_ref(), mock_env(), msg).unwrap_err();
+    }
+
+    #[test]
+    fn counters_follow_stored_services_and_executors() {
+        let mut deps = mock_dependencies();
+        let executor = |active: bool| Executor {
+            pubkey: None,
+            is_active: active,
+            joined_height: 0,
+            metadata: None,
+            jailed_until: None,
+        };
+        let addr = |name: &str| deps.api.addr_canonicalize(name).unwrap();
+        let (executor1, executor2, executor3) =
+            (addr("executor1"), addr("executor2"), addr("executor3"));
+        let storage = deps.as_mut().storage;
+
+        // storing an executor again counts it once, deactivating it keeps it in the total
+        store_executor(storage, &executor1, &executor(true)).unwrap();
+        store_executor(storage, &executor1, &executor(true)).unwrap();
+        store_executor(storage, &executor2, &executor(true)).unwrap();
+        store_executor(storage, &executor2, &executor(false)).unwrap();
+        assert_eq!(executor_count_read(storage), 2);
+        assert_eq!(active_executor_count_read(storage), 1);
+
+        // removing an executor that is not stored leaves the counts alone
+        remove_executor(storage, &executor3).unwrap();
+        remove_executor(storage, &executor2).unwrap();
+        remove_executor(storage, &executor2).unwrap();
+        assert_eq!(executor_count_read(storage), 1);
+        assert_eq!(active_executor_count_read(storage), 1);
+
+        let service_info = ServiceInfo {
+            owner: Addr::unchecked("owner"),
+            service: Service {
+                dsources: vec![],
+                tcases: vec![],
+                oscript_url: String::new(),
+                fee: None,
+                request_timeout: None,
+                hash_algorithm: HashAlgorithm::Sha256,
+                tree_format: None,
+            },
+            allowed_executors: None,
+        };
+        store_service_info(storage, b"service1", &service_info).unwrap();
+        store_service_info(storage, b"service1", &service_info).unwrap();
+        store_service_info(storage, b"service2", &service_info).unwrap();
+        remove_service_info(storage, b"service3").unwrap();
+        assert_eq!(service_count_read(storage), 2);
+
+        // contracts stored before the counters, executors were stored as [0] and are inactive
+        for key in [
+            KEY_SERVICE_COUNT,
+            KEY_EXECUTOR_COUNT,
+            KEY_ACTIVE_EXECUTOR_COUNT,
+        ] {
+            storage.remove(&to_length_prefixed(key));
+        }
+        let legacy_key = [to_length_prefixed(PREFIX_EXECUTOR), executor3.to_vec()].concat();
+        storage.set(&legacy_key, &[0]);
+        assert_eq!(executor_count_read(storage), 0);
+        migrate_counters(storage).unwrap();
+        assert_eq!(service_count_read(storage), 2);
+        assert_eq!(executor_count_read(storage), 2);
+        assert_eq!(active_executor_count_read(storage), 1);
+    }
+
+    #[test]
+    fn requests_stored_before_statuses_keep_their_merkle_root() {
+        let mut deps = mock_dependencies();
+        let msg = instantiate_msg(&["executor1", "executor2", "executor3"]);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        add_service(deps.as_mut(), "service", None);
+
+        // requests stored before statuses have no status and are missing from the status index
+        let merkle_root = "ab".repeat(32);
+        for (stage, merkle_root) in [(1, merkle_root.as_str()), (2, "")] {
+            let request = format!(
+                concat!(
+                    r#"{{"requester":"requester","request_height":12345,"#,
+                    r#""submit_merkle_height":0,"merkle_root":"{}","threshold":1,"#,
+                    r#""service":"service","input":null}}"#
+                ),
+                merkle_root
+            );
+            deps.storage.set(&requests().key(stage), request.as_bytes());
+        }
+        latest_stage_save(deps.as_mut().storage, &2).unwrap();
+        let migration = RequestMigration {
+            next_stage: 1,
+            last_stage: 2,
+        };
+        request_migration_save(deps.as_mut().storage, &migration).unwrap();
+
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            stage: 1,
+            merkle_root: "00".repeat(32),
+            executors: vec!["executor1".to_string()],
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::AlreadyFinished {});
+        let msg = ExecuteMsg::CancelRequest { stage: 1 };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::AlreadyFinished {});
+
+        let request = query_request(deps.as_ref(), &mock_env(), 1).unwrap();
+        assert_eq!(request.status, RequestStatus::Finalized);
+        assert_eq!(request.merkle_root, merkle_root);
+
+        // status queries find them before and after the migration
+        let stages = |deps: Deps, status: RequestStatus| -> (Vec<u64>, Vec<u64>) {
+            let by_status =
+                query_requests_by_status(deps, &mock_env(), status.clone(), None, None, Some(1))
+                    .unwrap();
+            let filter = RequestFilter {
+                status: Some(status),
+                ..Default::default()
+            };
+            let search =
+                query_search_requests(deps, &mock_env(), filter, None, None, Some(1)).unwrap();
+            (
+                by_status
+                    .items
+                    .iter()
+                    .map(|request| request.stage)
+                    .collect(),
+                search
+                    .requests
+                    .iter()
+                    .map(|request| request.stage)
+                    .collect(),
+            )
+        };
+        for migrated in [false, true] {
+            if migrated {
+                let msg = ExecuteMsg::MigrateRequests { limit: None };
+                execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+                assert!(are_requests_migrated(&deps.storage).unwrap());
+            }
+            assert_eq!(
+                stages(deps.as_ref(), RequestStatus::Finalized),
+                (vec![1], vec![1])
+            );
+            assert_eq!(
+                stages(deps.as_ref(), RequestStatus::Pending),
+                (vec![2], vec![2])
+            );
+        }
+    }
+
+    #[test]
+    fn callback_runs_with_gas_limit() {
+        let mut deps = mock_dependencies();
+        let msg = instantiate_msg(&["executor1", "executor2", "executor3"]);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        add_service(deps.as_mut(), "service", None);
+        let msg = ExecuteMsg::Request {
+            service: "service".to_string(),
+            input: None,
+            threshold: 1,
+            callback: Some(RequestCallback {
+                contract: "consumer".to_string(),
+                msg: None,
+            }),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("requester", &[]), msg).unwrap();
+
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            stage: 1,
+            merkle_root: "ab".repeat(32),
+            executors: vec!["executor1".to_string()],
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].gas_limit, Some(CALLBACK_GAS_LIMIT));
+        assert_eq!(res.messages[0].id, 1);
+        assert_eq!(res.messages[0].reply_on, ReplyOn::Error);
+        let callback = CallbackMsg::AioracleCallback {
+            stage: 1,
+            merkle_root: "ab".repeat(32),
+            msg: None,
+        };
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "consumer".to_string(),
+                msg: to_binary(&callback).unwrap(),
+                funds: vec![],
+            })
+        );
+
+        // a failing callback is reported without undoing the finalization
+        let msg = Reply {
+            id: 1,
+            result: SubMsgResult::Err("out of gas".to_string()),
+        };
+        let res = reply(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "callback_failed"),
+                attr("stage", "1"),
+                attr("error", "out of gas"),
+            ]
+        );
+        let request = query_request(deps.as_ref(), &mock_env(), 1).unwrap();
+        assert_eq!(request.status, RequestStatus::Finalized);
+    }
+
+    #[test]
+    fn migrate_keeps_owner_registration_of_older_configs() {
+        let mut deps = mock_dependencies();
+        let mut msg = instantiate_msg(&[]);
+        msg.owner_registration = None;
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert!(!config_read(&deps.storage).unwrap().owner_registration);
+
+        // config stored before the owner registration flag
+        deps.storage.set(
+            &to_length_prefixed(KEY_CONFIG),
+            br#"{"owner":"owner","max_req_threshold":67}"#,
+        );
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert!(config_read(&deps.storage).unwrap().owner_registration);
+    }
+
+    #[test]
+    fn request_escrows_fees_and_refunds_surplus() {
+        let mut deps = mock_dependencies();
+        let msg = instantiate_msg(&["executor1", "executor2", "executor3"]);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        add_service(deps.as_mut(), "service", Some(coin(150, "orai")));
+
+        let info = mock_info("requester", &[coin(200, "orai"), coin(5, "atom")]);
+        let res = execute(deps.as_mut(), mock_env(), info, request_msg("service", 1)).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "requester".to_string(),
+                amount: vec![coin(50, "orai"), coin(5, "atom")],
+            }
+            .into()
+        );
+        let request = requests().load(&deps.storage, 1).unwrap();
+        assert_eq!(request.fees, coins(150, "orai"));
+
+        // nothing to refund when the exact fees are sent
+        let info = mock_info("requester", &coins(150, "orai"));
+        let res = execute(deps.as_mut(), mock_env(), info, request_msg("service", 1)).unwrap();
+        assert!(res.messages.is_empty());
+
+        for funds in [coins(100, "orai"), coins(150, "atom"), vec![]] {
+            let info = mock_info("requester", &funds);
+            let err =
+                execute(deps.as_mut(), mock_env(), info, request_msg("service", 1)).unwrap_err();
+            assert_eq!(err, ContractError::InsufficientFunds {});
+        }
+    }
+
+    #[test]
+    fn finalized_fees_are_split_between_executors() {
+        let mut deps = mock_dependencies();
+        let msg = instantiate_msg(&["executor1", "executor2", "executor3"]);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        add_service(deps.as_mut(), "service", Some(coin(100, "orai")));
+        let info = mock_info("requester", &coins(100, "orai"));
+        execute(deps.as_mut(), mock_env(), info, request_msg("service", 1)).unwrap();
+
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            stage: 1,
+            merkle_root: "ab".repeat(32),
+            executors: vec![
+                "executor1".to_string(),
+                "executor2".to_string(),
+                "executor3".to_string(),
+            ],
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+
+        // the remainder of the split goes to the first executor
+        for (executor, reward) in [("executor1", 34), ("executor2", 33), ("executor3", 33)] {
+            let executor = deps.api.addr_canonicalize(executor).unwrap();
+            assert_eq!(
+                read_executor_rewards(&deps.storage, &executor),
+                coins(reward, "orai")
+            );
+        }
+
+        let msg = ExecuteMsg::ClaimReward {};
+        let res = execute(deps.as_mut(), mock_env(), mock_info("executor1", &[]), msg).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "executor1".to_string(),
+                amount: coins(34, "orai"),
+            }
+            .into()
+        );
+        let msg = ExecuteMsg::ClaimReward {};
+        let err = execute(deps.as_mut(), mock_env(), mock_info("executor1", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::NoReward {});
+    }
+
+    #[test]
+    fn reclaimed_and_cancelled_requests_refund_fees() {
+        let mut deps = mock_dependencies();
+        let mut msg = instantiate_msg(&["executor1", "executor2", "executor3"]);
+        msg.request_timeout = Some(10);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        add_service(deps.as_mut(), "service", Some(coin(100, "orai")));
+        for _ in 0..2 {
+            let info = mock_info("requester", &coins(100, "orai"));
+            execute(deps.as_mut(), mock_env(), info, request_msg("service", 1)).unwrap();
+        }
+        let refund = BankMsg::Send {
+            to_address: "requester".to_string(),
+            amount: coins(100, "orai"),
+        };
+
+        let msg = ExecuteMsg::ReclaimRequest { stage: 1 };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("someone", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("requester", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::RequestNotExpired {});
+        let mut env = mock_env();
+        env.block.height += 10;
+        let info = mock_info("requester", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap();
+        assert_eq!(res.messages[0].msg, refund.clone().into());
+        let request = requests().load(&deps.storage, 1).unwrap();
+        assert_eq!(request.status, RequestStatus::Expired);
+        assert!(request.fees.is_empty());
+        let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        assert_eq!(err, ContractError::AlreadyFinished {});
+
+        let msg = ExecuteMsg::CancelRequest { stage: 2 };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("requester", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let info = mock_info("owner", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap();
+        assert_eq!(res.messages[0].msg, refund.into());
+        let request = requests().load(&deps.storage, 2).unwrap();
+        assert_eq!(request.status, RequestStatus::Cancelled);
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, ContractError::AlreadyFinished {});
+    }
+
+    #[test]
+    fn timed_out_requests_take_no_merkle_root() {
+        let mut deps = mock_dependencies();
+        let mut msg = instantiate_msg(&["executor1", "executor2", "executor3"]);
+        msg.request_timeout = Some(10);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        add_service(deps.as_mut(), "service", None);
+        let msg = ExecuteMsg::AddService(AddServiceMsg {
+            service_name: "quick".to_string(),
+            service: Service {
+                dsources: vec![],
+                tcases: vec![],
+                oscript_url: String::new(),
+                fee: None,
+                request_timeout: Some(5),
+                hash_algorithm: HashAlgorithm::Sha256,
+                tree_format: None,
+            },
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        for service in ["service", "quick"] {
+            let msg = request_msg(service, 1);
+            execute(deps.as_mut(), mock_env(), mock_info("requester", &[]), msg).unwrap();
+        }
+        let height = mock_env().block.height;
+        let env_at = |height: u64| {
+            let mut env = mock_env();
+            env.block.height = height;
+            env
+        };
+
+        // the timeout of the service replaces the one of the config
+        let timeout_heights: Vec<Option<u64>> = (1..=2)
+            .map(|stage| query_request(deps.as_ref(), &mock_env(), stage))
+            .map(|request| request.unwrap().timeout_height)
+            .collect();
+        assert_eq!(timeout_heights, vec![Some(height + 10), Some(height + 5)]);
+        let status = |deps: Deps, stage: u64, height: u64| {
+            query_request(deps, &env_at(height), stage).unwrap().status
+        };
+        assert_eq!(status(deps.as_ref(), 2, height + 4), RequestStatus::Pending);
+        assert_eq!(status(deps.as_ref(), 2, height + 5), RequestStatus::Expired);
+
+        // expired before and after the requester reclaims the fees
+        let register = |deps: DepsMut, height: u64| {
+            let msg = ExecuteMsg::RegisterMerkleRoot {
+                stage: 2,
+                merkle_root: "ab".repeat(32),
+                executors: vec!["executor1".to_string()],
+            };
+            execute(deps, env_at(height), mock_info("owner", &[]), msg)
+        };
+        let err = register(deps.as_mut(), height + 5).unwrap_err();
+        assert_eq!(err, ContractError::RequestExpired {});
+        let msg = ExecuteMsg::ReclaimRequest { stage: 2 };
+        execute(
+            deps.as_mut(),
+            env_at(height + 5),
+            mock_info("requester", &[]),
+            msg,
+        )
+        .unwrap();
+        let err = register(deps.as_mut(), height + 6).unwrap_err();
+        assert_eq!(err, ContractError::RequestExpired {});
+
+        // the request with the longer timeout is still served
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            stage: 1,
+            merkle_root: "ab".repeat(32),
+            executors: vec!["executor1".to_string()],
+        };
+        execute(
+            deps.as_mut(),
+            env_at(height + 9),
+            mock_info("owner", &[]),
+            msg,
+        )
+        .unwrap();
+        assert_eq!(
+            status(deps.as_ref(), 1, height + 20),
+            RequestStatus::Finalized
+        );
+    }
+
+    fn signing_key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32]).unwrap()
+    }
+
+    fn public_key(key: &SigningKey) -> Binary {
+        Binary::from(key.verifying_key().to_bytes().as_slice())
+    }
+
+    /// Signs sha256 of the message, as verified by the contract.
+    fn sign(key: &SigningKey, message: &[u8]) -> Binary {
+        let signature: Signature = key.sign(message);
+        Binary::from(signature.as_ref())
+    }
+
+    fn rotate_executor_key(deps: DepsMut, executor: &str, key: &SigningKey) {
+        let pubkey = public_key(key);
+        let message = [
+            mock_env().contract.address.as_bytes(),
+            executor.as_bytes(),
+            pubkey.as_slice(),
+        ]
+        .concat();
+        let msg = ExecuteMsg::RotateExecutorKey {
+            signature: sign(key, &message),
+            pubkey,
+        };
+        execute(deps, mock_env(), mock_info(executor, &[]), msg).unwrap();
+    }
+
+    fn sign_merkle_root(
+        executor: &str,
+        key: &SigningKey,
+        stage: u64,
+        merkle_root: &[u8; 32],
+    ) -> ExecutorSignature {
+        let message = [
+            mock_env().contract.address.as_bytes(),
+            &stage.to_be_bytes(),
+            merkle_root,
+        ]
+        .concat();
+        ExecutorSignature {
+            executor: executor.to_string(),
+            pubkey: None,
+            signature: sign(key, &message),
+        }
+    }
+
+    #[test]
+    fn executor_keys_are_rotated_by_their_holder() {
+        let mut deps = mock_dependencies();
+        let msg = instantiate_msg(&["executor1"]);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        let (key, other_key) = (signing_key(1), signing_key(2));
+        let rotate = |executor: &str, pubkey: Binary, signing_key: &SigningKey| {
+            let message = [
+                mock_env().contract.address.as_bytes(),
+                executor.as_bytes(),
+                pubkey.as_slice(),
+            ]
+            .concat();
+            ExecuteMsg::RotateExecutorKey {
+                signature: sign(signing_key, &message),
+                pubkey,
+            }
+        };
+
+        // only executors rotate, with a compressed key signing its own rotation
+        let msg = rotate("stranger", public_key(&key), &key);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("stranger", &[]), msg);
+        assert_eq!(err.unwrap_err(), ContractError::Unauthorized {});
+        let msg = rotate("executor1", Binary::from(vec![2; 32]), &key);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("executor1", &[]), msg);
+        assert_eq!(err.unwrap_err(), ContractError::WrongLength {});
+        let msg = rotate("executor1", public_key(&key), &other_key);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("executor1", &[]), msg);
+        assert_eq!(err.unwrap_err(), ContractError::VerificationFailed {});
+        // a rotation signed for another executor can not be replayed
+        let msg = rotate("executor2", public_key(&key), &key);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("executor1", &[]), msg);
+        assert_eq!(err.unwrap_err(), ContractError::VerificationFailed {});
+
+        let msg = rotate("executor1", public_key(&key), &key);
+        execute(deps.as_mut(), mock_env(), mock_info("executor1", &[]), msg).unwrap();
+        let msg = QueryMsg::GetExecutor {
+            address: "executor1".to_string(),
+        };
+        let executor: ExecutorsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg.clone()).unwrap()).unwrap();
+        assert_eq!(executor.pubkey, Some(public_key(&key)));
+        rotate_executor_key(deps.as_mut(), "executor1", &other_key);
+        let executor: ExecutorsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(executor.pubkey, Some(public_key(&other_key)));
+    }
+
+    #[test]
+    fn merkle_roots_are_registered_once_signed_by_threshold() {
+        let mut deps = mock_dependencies();
+        let msg = instantiate_msg(&["executor1", "executor2", "executor3"]);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        let keys: Vec<SigningKey> = (1..=3).map(signing_key).collect();
+        for (executor, key) in ["executor1", "executor2", "executor3"].iter().zip(&keys) {
+            rotate_executor_key(deps.as_mut(), executor, key);
+        }
+        add_service(deps.as_mut(), "service", None);
+        let msg = request_msg("service", 2);
+        execute(deps.as_mut(), mock_env(), mock_info("requester", &[]), msg).unwrap();
+        let submit = |merkle_root: &[u8; 32], signatures| ExecuteMsg::SubmitMerkleRoot {
+            stage: 1,
+            merkle_root: hex::encode(merkle_root),
+            signatures,
+        };
+        let root = [0xab; 32];
+        let competing_root = [0xcd; 32];
+
+        let msg = submit(
+            &competing_root,
+            vec![sign_merkle_root("executor3", &keys[2], 1, &competing_root)],
+        );
+        execute(deps.as_mut(), mock_env(), mock_info("relayer", &[]), msg).unwrap();
+        assert_eq!(
+            read_merkle_root_signers(&deps.storage, 1, &competing_root),
+            vec![Addr::unchecked("executor3")]
+        );
+
+        // signatures with the wrong key or from outside the executor set are rejected
+        let msg = submit(
+            &root,
+            vec![sign_merkle_root("executor2", &keys[0], 1, &root)],
+        );
+        let err = execute(deps.as_mut(), mock_env(), mock_info("relayer", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::VerificationFailed {});
+        let mut signature = sign_merkle_root("executor2", &keys[1], 1, &root);
+        signature.pubkey = Some(public_key(&keys[0]));
+        let msg = submit(&root, vec![signature]);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("relayer", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidSignature {});
+        let msg = submit(
+            &root,
+            vec![sign_merkle_root("stranger", &keys[0], 1, &root)],
+        );
+        let err = execute(deps.as_mut(), mock_env(), mock_info("relayer", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // the same executor counts once
+        let signature = sign_merkle_root("executor1", &keys[0], 1, &root);
+        let msg = submit(&root, vec![signature.clone(), signature.clone()]);
+        let res = execute(deps.as_mut(), mock_env(), mock_info("relayer", &[]), msg).unwrap();
+        assert!(res.attributes.contains(&attr("signatures", "1")));
+        let msg = submit(&root, vec![signature]);
+        let res = execute(deps.as_mut(), mock_env(), mock_info("relayer", &[]), msg).unwrap();
+        assert!(res.attributes.contains(&attr("signatures", "1")));
+        assert_eq!(
+            query_request(deps.as_ref(), &mock_env(), 1).unwrap().status,
+            RequestStatus::Pending
+        );
+
+        let msg = submit(
+            &root,
+            vec![sign_merkle_root("executor2", &keys[1], 1, &root)],
+        );
+        let res = execute(deps.as_mut(), mock_env(), mock_info("relayer", &[]), msg).unwrap();
+        assert!(res.attributes.contains(&attr("signatures", "2")));
+        let request = query_request(deps.as_ref(), &mock_env(), 1).unwrap();
+        assert_eq!(request.status, RequestStatus::Finalized);
+        assert_eq!(request.merkle_root, hex::encode(root));
+        assert_eq!(
+            request.executors,
+            vec![Addr::unchecked("executor1"), Addr::unchecked("executor2")]
+        );
+        // the signers of the competing root are cleared
+        assert!(read_merkle_root_signers(&deps.storage, 1, &competing_root).is_empty());
+
+        let msg = submit(
+            &root,
+            vec![sign_merkle_root("executor3", &keys[2], 1, &root)],
+        );
+        let err = execute(deps.as_mut(), mock_env(), mock_info("relayer", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::AlreadyFinished {});
+    }
+
+    #[test]
+    fn signers_removed_or_jailed_after_signing_no_longer_count() {
+        let mut deps = mock_dependencies();
+        let msg = instantiate_msg(&["executor1", "executor2", "executor3"]);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        let keys: Vec<SigningKey> = (1..=3).map(signing_key).collect();
+        for (executor, key) in ["executor1", "executor2", "executor3"].iter().zip(&keys) {
+            rotate_executor_key(deps.as_mut(), executor, key);
+        }
+        add_service(deps.as_mut(), "service", None);
+        let msg = request_msg("service", 2);
+        execute(deps.as_mut(), mock_env(), mock_info("requester", &[]), msg).unwrap();
+        let root = [0xab; 32];
+        let submit = |deps: DepsMut, executor: &str, key: &SigningKey| {
+            let msg = ExecuteMsg::SubmitMerkleRoot {
+                stage: 1,
+                merkle_root: hex::encode(root),
+                signatures: vec![sign_merkle_root(executor, key, 1, &root)],
+            };
+            execute(deps, mock_env(), mock_info("relayer", &[]), msg).unwrap()
+        };
+        submit(deps.as_mut(), "executor1", &keys[0]);
+
+        let msg = ExecuteMsg::UpdateConfig {
+            update_config_msg: UpdateConfigMsg {
+                old_executors: Some(vec!["executor1".to_string()]),
+                ..Default::default()
+            },
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        let res = submit(deps.as_mut(), "executor2", &keys[1]);
+        assert!(res.attributes.contains(&attr("signatures", "1")));
+
+        let executor2 = deps.api.addr_canonicalize("executor2").unwrap();
+        let mut executor_info = read_executor(&deps.storage, &executor2).unwrap();
+        executor_info.jailed_until = Some(mock_env().block.height + 10);
+        store_executor(deps.as_mut().storage, &executor2, &executor_info).unwrap();
+        let res = submit(deps.as_mut(), "executor3", &keys[2]);
+        assert!(res.attributes.contains(&attr("signatures", "1")));
+        assert_eq!(
+            read_merkle_root_signers(&deps.storage, 1, &root),
+            vec![Addr::unchecked("executor3")]
+        );
+        assert_eq!(
+            query_request(deps.as_ref(), &mock_env(), 1).unwrap().status,
+            RequestStatus::Pending
+        );
+    }
+
+    #[test]
+    fn executor_signature_without_registered_key_needs_account_key() {
+        let deps = mock_dependencies();
+        let key = signing_key(1);
+        let pubkey = public_key(&key);
+        // account addresses are ripemd160(sha256(pubkey))
+        let pubkey_hash = sha2::Sha256::digest(pubkey.as_slice());
+        let executor = CanonicalAddr::from(
+            <ripemd::Ripemd160 as ripemd::Digest>::digest(pubkey_hash).as_slice(),
+        );
+        let executor_info = Executor {
+            pubkey: None,
+            is_active: true,
+            joined_height: 0,
+            metadata: None,
+            jailed_until: None,
+        };
+        let message = b"merkle root";
+        let message_hash: [u8; 32] = sha2::Sha256::digest(message).into();
+        let signature = sign(&key, message);
+
+        verify_executor_signature(
+            &deps.api,
+            &executor,
+            &executor_info,
+            &message_hash,
+            &signature,
+            Some(&pubkey),
+        )
+        .unwrap();
+        // the key must be given and belong to the executor account
+        let err = verify_executor_signature(
+            &deps.api,
+            &executor,
+            &executor_info,
+            &message_hash,
+            &signature,
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::InvalidSignature {});
+        let other_key = signing_key(2);
+        let err = verify_executor_signature(
+            &deps.api,
+            &executor,
+            &executor_info,
+            &message_hash,
+            &sign(&other_key, message),
+            Some(&public_key(&other_key)),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::InvalidSignature {});
+        let err = verify_executor_signature(
+            &deps.api,
+            &executor,
+            &executor_info,
+            &message_hash,
+            &sign(&other_key, message),
+            Some(&pubkey),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::VerificationFailed {});
+    }
+
+    #[test]
+    fn upheld_challenges_slash_and_jail_executors() {
+        for burn_slashed in [false, true] {
+            let mut deps = mock_dependencies();
+            let mut msg = instantiate_msg(&["executor1", "executor2", "executor3"]);
+            msg.min_executor_bond = Some(coin(100, "orai"));
+            msg.challenge_period = Some(5);
+            msg.unbonding_period = Some(5);
+            msg.slash_fraction = Some(Decimal::percent(10));
+            msg.jail_period = Some(10);
+            msg.burn_slashed = Some(burn_slashed);
+            instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+            assert_eq!(
+                query_executor_size(deps.as_ref(), mock_env().block.height).unwrap(),
+                0
+            );
+            for (executor, bond) in [("executor1", 200), ("executor2", 100), ("executor3", 100)] {
+                let info = mock_info(executor, &coins(bond, "orai"));
+                execute(deps.as_mut(), mock_env(), info, ExecuteMsg::BondExecutor {}).unwrap();
+            }
+            let msg = ExecuteMsg::UnbondExecutor {
+                amount: Uint128::new(50),
+            };
+            execute(deps.as_mut(), mock_env(), mock_info("executor1", &[]), msg).unwrap();
+            assert_eq!(
+                query_executor_size(deps.as_ref(), mock_env().block.height).unwrap(),
+                3
+            );
+
+            add_service(deps.as_mut(), "service", None);
+            let msg = request_msg("service", 2);
+            execute(deps.as_mut(), mock_env(), mock_info("requester", &[]), msg).unwrap();
+            let msg = ExecuteMsg::RegisterMerkleRoot {
+                stage: 1,
+                merkle_root: "ab".repeat(32),
+                executors: vec!["executor1".to_string()],
+            };
+            execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+            let msg = ExecuteMsg::ChallengeMerkleRoot {
+                stage: 1,
+                evidence: "wrong result".to_string(),
+            };
+            execute(deps.as_mut(), mock_env(), mock_info("executor2", &[]), msg).unwrap();
+            let msg = ExecuteMsg::ResolveChallenge {
+                stage: 1,
+                upheld: true,
+            };
+            let res = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+
+            // the slash fraction is taken from the bonded and the unbonding funds
+            let bond = query_executor_bond(deps.as_ref(), "executor1".to_string()).unwrap();
+            assert_eq!(bond.bonded, Uint128::new(135));
+            assert_eq!(bond.unbonding[0].amount, Uint128::new(45));
+            let jailed_until = mock_env().block.height + 10;
+            assert_eq!(
+                res.events,
+                vec![Event::new("slash")
+                    .add_attribute("executor", "executor1")
+                    .add_attribute("amount", "20orai")
+                    .add_attribute("jailed_until", jailed_until.to_string())]
+            );
+            let executor2 = deps.api.addr_canonicalize("executor2").unwrap();
+            if burn_slashed {
+                assert_eq!(
+                    res.messages[0].msg,
+                    BankMsg::Burn {
+                        amount: coins(20, "orai")
+                    }
+                    .into()
+                );
+                assert!(read_executor_rewards(&deps.storage, &executor2).is_empty());
+            } else {
+                assert!(res.messages.is_empty());
+                assert_eq!(
+                    read_executor_rewards(&deps.storage, &executor2),
+                    coins(20, "orai")
+                );
+            }
+            let request = query_request(deps.as_ref(), &mock_env(), 1).unwrap();
+            assert_eq!(request.status, RequestStatus::Pending);
+            assert!(request.merkle_root.is_empty());
+
+            // the jailed executor leaves the set, which lowers the reachable threshold
+            let executor = query_executor(
+                deps.as_ref(),
+                mock_env().block.height,
+                "executor1".to_string(),
+            )
+            .unwrap();
+            assert!(!executor.is_active);
+            assert_eq!(executor.jailed_until, Some(jailed_until));
+            assert_eq!(
+                query_executor_size(deps.as_ref(), mock_env().block.height).unwrap(),
+                2
+            );
+            let msg = request_msg("service", 2);
+            let err =
+                execute(deps.as_mut(), mock_env(), mock_info("requester", &[]), msg).unwrap_err();
+            assert_eq!(err, ContractError::InvalidThreshold {});
+
+            let msg = ExecuteMsg::UnjailExecutor {};
+            let err = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("executor1", &[]),
+                msg.clone(),
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::StillJailed {});
+            let mut env = mock_env();
+            env.block.height = jailed_until;
+            let info = mock_info("executor1", &[]);
+            execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap();
+            assert!(
+                query_executor(
+                    deps.as_ref(),
+                    mock_env().block.height,
+                    "executor1".to_string()
+                )
+                .unwrap()
+                .is_active
+            );
+            assert_eq!(
+                query_executor_size(deps.as_ref(), mock_env().block.height).unwrap(),
+                3
+            );
+            let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
+            assert_eq!(err, ContractError::NotJailed {});
+        }
+    }
+
+    #[test]
+    fn challenged_executors_cannot_get_their_bond_out_before_resolution() {
+        let mut deps = mock_dependencies();
+        let mut msg = instantiate_msg(&["executor1", "executor2", "executor3"]);
+        msg.min_executor_bond = Some(coin(100, "orai"));
+        msg.challenge_period = Some(5);
+        msg.unbonding_period = Some(5);
+        msg.slash_fraction = Some(Decimal::percent(50));
+        msg.burn_slashed = Some(true);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        for executor in ["executor1", "executor2", "executor3"] {
+            let info = mock_info(executor, &coins(100, "orai"));
+            execute(deps.as_mut(), mock_env(), info, ExecuteMsg::BondExecutor {}).unwrap();
+        }
+        add_service(deps.as_mut(), "service", None);
+        let msg = request_msg("service", 1);
+        execute(deps.as_mut(), mock_env(), mock_info("requester", &[]), msg).unwrap();
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            stage: 1,
+            merkle_root: "ab".repeat(32),
+            executors: vec!["executor1".to_string()],
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        let unbond = |amount| ExecuteMsg::UnbondExecutor {
+            amount: Uint128::new(amount),
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("executor1", &[]),
+            unbond(60),
+        )
+        .unwrap();
+
+        let mut env = mock_env();
+        env.block.height += 4;
+        let msg = ExecuteMsg::ChallengeMerkleRoot {
+            stage: 1,
+            evidence: "wrong result".to_string(),
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("executor2", &[]), msg).unwrap();
+
+        // neither the unbonding nor the bonded funds can leave before the challenge is resolved
+        env.block.height += 1;
+        let info = mock_info("executor1", &[]);
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::WithdrawUnbonded {},
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::OpenChallenges {});
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), unbond(40)).unwrap_err();
+        assert_eq!(err, ContractError::OpenChallenges {});
+
+        let msg = ExecuteMsg::ResolveChallenge {
+            stage: 1,
+            upheld: true,
+        };
+        let res = execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), msg).unwrap();
+        assert_eq!(res.events[0].attributes[1], attr("amount", "50orai"),);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::WithdrawUnbonded {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "executor1".to_string(),
+                amount: coins(30, "orai"),
+            }
+            .into()
+        );
+        execute(deps.as_mut(), env, info, unbond(20)).unwrap();
+    }
+
+    #[test]
+    fn requests_are_served_by_the_executor_set_they_were_made_with() {
+        let mut deps = mock_dependencies();
+        let msg = instantiate_msg(&["executor1", "executor2", "executor3"]);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        add_service(deps.as_mut(), "service", None);
+        let msg = request_msg("service", 1);
+        execute(deps.as_mut(), mock_env(), mock_info("requester", &[]), msg).unwrap();
+        let version = requests()
+            .load(&deps.storage, 1)
+            .unwrap()
+            .executor_set_version
+            .unwrap();
+
+        let msg = ExecuteMsg::UpdateConfig {
+            update_config_msg: UpdateConfigMsg {
+                new_executors: Some(vec!["executor4".to_string()]),
+                old_executors: Some(vec!["executor3".to_string()]),
+                ..Default::default()
+            },
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        let msg = request_msg("service", 1);
+        execute(deps.as_mut(), mock_env(), mock_info("requester", &[]), msg).unwrap();
+
+        let in_set = |deps: Deps, executor: &str, version: u64| {
+            let msg = QueryMsg::CheckExecutorInSet {
+                address: executor.to_string(),
+                version,
+            };
+            from_binary::<bool>(&query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+        assert!(in_set(deps.as_ref(), "executor3", version));
+        assert!(!in_set(deps.as_ref(), "executor4", version));
+        assert!(!in_set(deps.as_ref(), "executor3", u64::MAX));
+        assert!(in_set(deps.as_ref(), "executor4", u64::MAX));
+
+        // the removed executor still serves the request made before its removal, the new one
+        // only the later requests
+        let register = |stage: u64, executor: &str| ExecuteMsg::RegisterMerkleRoot {
+            stage,
+            merkle_root: "ab".repeat(32),
+            executors: vec![executor.to_string()],
+        };
+        for (stage, executor) in [(1, "executor4"), (2, "executor3")] {
+            let msg = register(stage, executor);
+            let err = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap_err();
+            assert_eq!(err, ContractError::Unauthorized {});
+        }
+        for (stage, executor) in [(1, "executor3"), (2, "executor4")] {
+            let msg = register(stage, executor);
+            execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        }
+    }
+
+    fn apply_as_executor(
+        deps: DepsMut,
+        applicant: &str,
+        key: &SigningKey,
+        metadata: &str,
+    ) -> Result<Response, ContractError> {
+        let pubkey = public_key(key);
+        let message = [
+            mock_env().contract.address.as_bytes(),
+            applicant.as_bytes(),
+            pubkey.as_slice(),
+        ]
+        .concat();
+        let msg = ExecuteMsg::ApplyAsExecutor {
+            signature: sign(key, &message),
+            pubkey,
+            metadata: Some(metadata.to_string()),
+        };
+        execute(deps, mock_env(), mock_info(applicant, &[]), msg)
+    }
+
+    #[test]
+    fn executor_applications_are_decided_by_the_owner() {
+        let mut deps = mock_dependencies();
+        let mut msg = instantiate_msg(&["executor1"]);
+        msg.epoch_length = Some(10);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        let err =
+            apply_as_executor(deps.as_mut(), "executor1", &signing_key(1), "gpu").unwrap_err();
+        assert_eq!(err, ContractError::AlreadyExecutor {});
+
+        // applying again replaces the pending application
+        apply_as_executor(deps.as_mut(), "applicant1", &signing_key(1), "cpu").unwrap();
+        apply_as_executor(deps.as_mut(), "applicant1", &signing_key(2), "gpu").unwrap();
+        apply_as_executor(deps.as_mut(), "applicant2", &signing_key(3), "gpu").unwrap();
+        apply_as_executor(deps.as_mut(), "applicant3", &signing_key(4), "gpu").unwrap();
+        let applications = query_executor_applications(deps.as_ref(), None, None).unwrap();
+        assert_eq!(applications.items.len(), 3);
+        let application =
+            query_executor_application(deps.as_ref(), "applicant1".to_string()).unwrap();
+        assert_eq!(application.pubkey, public_key(&signing_key(2)));
+        assert_eq!(application.metadata, Some("gpu".to_string()));
+        assert!(!application.approved);
+
+        let approve = |applicant: &str| ExecuteMsg::ApproveExecutor {
+            applicant: applicant.to_string(),
+        };
+        let reject = |applicant: &str| ExecuteMsg::RejectExecutor {
+            applicant: applicant.to_string(),
+        };
+        for msg in [approve("applicant1"), reject("applicant1")] {
+            let info = mock_info("executor1", &[]);
+            let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+            assert_eq!(err, ContractError::Unauthorized {});
+        }
+        let owner = mock_info("owner", &[]);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            owner.clone(),
+            reject("applicant3"),
+        )
+        .unwrap();
+        for msg in [approve("applicant3"), reject("applicant3")] {
+            let err = execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap_err();
+            assert_eq!(err, ContractError::ApplicationNotFound {});
+        }
+
+        // approved applicants wait for the next epoch to join
+        for applicant in ["applicant1", "applicant2"] {
+            execute(deps.as_mut(), mock_env(), owner.clone(), approve(applicant)).unwrap();
+        }
+        for msg in [approve("applicant1"), reject("applicant1")] {
+            let err = execute(deps.as_mut(), mock_env(), owner.clone(), msg).unwrap_err();
+            assert_eq!(err, ContractError::InvalidInput {});
+        }
+        let err =
+            apply_as_executor(deps.as_mut(), "applicant1", &signing_key(1), "cpu").unwrap_err();
+        assert_eq!(err, ContractError::AlreadyExecutor {});
+        let height = mock_env().block.height;
+        let epoch_executors = |deps: Deps, next: bool| {
+            let mut executors = query_epoch_executors(deps, height, next, None, None)
+                .unwrap()
+                .executors;
+            executors.sort();
+            executors
+        };
+        assert_eq!(epoch_executors(deps.as_ref(), false), vec!["executor1"]);
+        assert_eq!(
+            epoch_executors(deps.as_ref(), true),
+            vec!["applicant1", "applicant2", "executor1"]
+        );
+
+        // removing an approved applicant drops both its application and its queued join
+        let msg = ExecuteMsg::UpdateConfig {
+            update_config_msg: UpdateConfigMsg {
+                old_executors: Some(vec!["applicant2".to_string()]),
+                ..Default::default()
+            },
+        };
+        execute(deps.as_mut(), mock_env(), owner, msg).unwrap();
+        assert!(query_executor_application(deps.as_ref(), "applicant2".to_string()).is_err());
+        assert_eq!(
+            epoch_executors(deps.as_ref(), true),
+            vec!["applicant1", "executor1"]
+        );
+
+        // the first transaction of the epoch adds the executor with its application
+        let mut env = mock_env();
+        env.block.height = (height / 10 + 1) * 10;
+        let msg = ExecuteMsg::UpdateConfig {
+            update_config_msg: UpdateConfigMsg::default(),
+        };
+        execute(deps.as_mut(), env, mock_info("owner", &[]), msg).unwrap();
+        let executor = query_executor(
+            deps.as_ref(),
+            mock_env().block.height,
+            "applicant1".to_string(),
+        )
+        .unwrap();
+        assert!(executor.is_active);
+        assert_eq!(executor.pubkey, Some(public_key(&signing_key(2))));
+        assert_eq!(executor.metadata, Some("gpu".to_string()));
+        assert!(query_executor(
+            deps.as_ref(),
+            mock_env().block.height,
+            "applicant2".to_string()
+        )
+        .is_err());
+        let applications = query_executor_applications(deps.as_ref(), None, None).unwrap();
+        assert!(applications.items.is_empty());
+    }
+
+    #[test]
+    fn services_can_be_restricted_to_some_executors() {
+        let mut deps = mock_dependencies();
+        let executors = [
+            "executor1",
+            "executor2",
+            "executor3",
+            "executor4",
+            "executor5",
+        ];
+        let mut msg = instantiate_msg(&executors);
+        msg.min_executor_bond = Some(coin(100, "orai"));
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        // executor4 stays without bond
+        for executor in ["executor1", "executor2", "executor3", "executor5"] {
+            let info = mock_info(executor, &coins(100, "orai"));
+            execute(deps.as_mut(), mock_env(), info, ExecuteMsg::BondExecutor {}).unwrap();
+        }
+        let executor3 = deps.api.addr_canonicalize("executor3").unwrap();
+        let mut executor_info = read_executor(&deps.storage, &executor3).unwrap();
+        executor_info.jailed_until = Some(mock_env().block.height + 100);
+        store_executor(deps.as_mut().storage, &executor3, &executor_info).unwrap();
+        update_executor_status(deps.as_mut().storage, &executor3).unwrap();
+        assert_eq!(
+            query_executor_size(deps.as_ref(), mock_env().block.height).unwrap(),
+            3
+        );
+
+        add_service(deps.as_mut(), "service", None);
+        let update_service = |allowed_executors: &[&str]| {
+            ExecuteMsg::UpdateService(UpdateServiceMsg {
+                service_name: "service".to_string(),
+                new_owner: None,
+                dsources: None,
+                tcases: None,
+                oscript_url: None,
+                fee: None,
+                request_timeout: None,
+                hash_algorithm: None,
+                tree_format: None,
+                allowed_executors: Some(
+                    allowed_executors
+                        .iter()
+                        .map(|executor| executor.to_string())
+                        .collect(),
+                ),
+            })
+        };
+        let msg = update_service(&["executor1", "executor2", "executor3", "executor4", "other"]);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("requester", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+
+        // the jailed, unbonded and unknown executors of the list are left out of its size
+        let allowed_executors = read_service_info(&deps.storage, b"service")
+            .unwrap()
+            .allowed_executors
+            .unwrap();
+        assert_eq!(
+            query_allowed_executor_size(deps.as_ref(), &allowed_executors).unwrap(),
+            2
+        );
+        let msg = request_msg("service", 1);
+        execute(deps.as_mut(), mock_env(), mock_info("requester", &[]), msg).unwrap();
+        let stage = latest_stage_read(&deps.storage).unwrap();
+        let register = |stage: u64, executor: &str| ExecuteMsg::RegisterMerkleRoot {
+            stage,
+            merkle_root: "ab".repeat(32),
+            executors: vec![executor.to_string()],
+        };
+        let msg = register(stage, "executor5");
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let msg = register(stage, "executor1");
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        // 2 of the 3 active executors are allowed, below the threshold
+        let msg = request_msg("service", 2);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("requester", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidThreshold {});
+
+        // an empty list opens the service to all executors again
+        let msg = update_service(&[]);
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        let service_info = read_service_info(&deps.storage, b"service").unwrap();
+        assert_eq!(service_info.allowed_executors, None);
+        let msg = request_msg("service", 2);
+        execute(deps.as_mut(), mock_env(), mock_info("requester", &[]), msg).unwrap();
+        let stage = latest_stage_read(&deps.storage).unwrap();
+        let msg = register(stage, "executor5");
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn results_are_stored_once_proven_against_the_merkle_root() {
+        let mut deps = mock_dependencies();
+        let mut msg = instantiate_msg(&["executor1", "executor2", "executor3"]);
+        msg.challenge_period = Some(10);
+        msg.unbonding_period = Some(10);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        add_service(deps.as_mut(), "service", None);
+        for _ in 0..2 {
+            let msg = request_msg("service", 1);
+            execute(deps.as_mut(), mock_env(), mock_info("requester", &[]), msg).unwrap();
+        }
+        let leaves: Vec<Binary> = (0..=MAX_RESULTS)
+            .map(|i| Binary::from(format!("result {}", i).into_bytes()))
+            .collect();
+        let tree = MerkleTree::new(HashAlgorithm::Sha256, TreeFormat::V1, &leaves);
+        let submit = |stage: u64, index: usize| ExecuteMsg::SubmitResult {
+            stage,
+            data: leaves[index].clone(),
+            proof: tree.hex_proof(index),
+        };
+        let requester = mock_info("requester", &[]);
+
+        let err = execute(deps.as_mut(), mock_env(), requester.clone(), submit(1, 0)).unwrap_err();
+        assert_eq!(err, ContractError::InvalidRequestStatus {});
+        for stage in 1..=2 {
+            let msg = ExecuteMsg::RegisterMerkleRoot {
+                stage,
+                merkle_root: tree.hex_root().unwrap(),
+                executors: vec!["executor1".to_string()],
+            };
+            execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        }
+
+        // only the requester and the executors of the request submit results
+        for sender in ["executor2", "owner"] {
+            let info = mock_info(sender, &[]);
+            let err = execute(deps.as_mut(), mock_env(), info, submit(1, 0)).unwrap_err();
+            assert_eq!(err, ContractError::Unauthorized {});
+        }
+        let info = mock_info("executor1", &[]);
+        execute(deps.as_mut(), mock_env(), info, submit(1, 0)).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), requester.clone(), submit(1, 0)).unwrap_err();
+        assert_eq!(err, ContractError::AlreadySubmitted {});
+
+        let msg = ExecuteMsg::SubmitResult {
+            stage: 1,
+            data: leaves[1].clone(),
+            proof: tree.hex_proof(2),
+        };
+        let err = execute(deps.as_mut(), mock_env(), requester.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::VerificationFailed {});
+        let msg = ExecuteMsg::SubmitResult {
+            stage: 1,
+            data: Binary::from(vec![0; MAX_RESULT_SIZE + 1]),
+            proof: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), requester.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::ResultTooLarge {
+                max_size: MAX_RESULT_SIZE
+            }
+        );
+
+        // results can still be added once the root is final, up to the limit
+        let mut env = mock_env();
+        env.block.height += 10;
+        let msg = ExecuteMsg::FinalizeMerkleRoot { stage: 1 };
+        execute(deps.as_mut(), env, mock_info("anyone", &[]), msg).unwrap();
+        for index in 1..MAX_RESULTS {
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                requester.clone(),
+                submit(1, index),
+            )
+            .unwrap();
+        }
+        let msg = submit(1, MAX_RESULTS);
+        let err = execute(deps.as_mut(), mock_env(), requester.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::TooManyResults {
+                max_results: MAX_RESULTS
+            }
+        );
+        let result = query_request_result(deps.as_ref(), 1).unwrap();
+        assert_eq!(result.results, leaves[..MAX_RESULTS].to_vec());
+
+        // a challenged root takes no results
+        let msg = ExecuteMsg::ChallengeMerkleRoot {
+            stage: 2,
+            evidence: "wrong result".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("executor2", &[]), msg).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), requester, submit(2, 0)).unwrap_err();
+        assert_eq!(err, ContractError::InvalidRequestStatus {});
+    }
+
+    #[test]
+    fn requests_are_searched_with_combined_filters() {
+        let mut deps = mock_dependencies();
+        let msg = instantiate_msg(&["executor1", "executor2", "executor3"]);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        add_service(deps.as_mut(), "a", None);
+        add_service(deps.as_mut(), "b", None);
+        add_service(deps.as_mut(), "spam", None);
+        let height = mock_env().block.height;
+        let mut request_at = |offset: u64, requester: &str, service: &str| {
+            let mut env = mock_env();
+            env.block.height = height + offset;
+            let msg = request_msg(service, 1);
+            execute(deps.as_mut(), env, mock_info(requester, &[]), msg).unwrap();
+        };
+        for (offset, requester, service) in [
+            (0, "requester1", "a"),
+            (1, "requester1", "b"),
+            (2, "requester2", "a"),
+            (3, "requester2", "b"),
+            (4, "requester1", "a"),
+            (4, "requester1", "a"),
+        ] {
+            request_at(offset, requester, service);
+        }
+        for _ in 0..MAX_SEARCH_SCAN {
+            request_at(5, "spammer", "spam");
+        }
+        request_at(6, "requester1", "b");
+        let last_stage = 6 + MAX_SEARCH_SCAN as u64 + 1;
+        for stage in [1, 5] {
+            let msg = ExecuteMsg::RegisterMerkleRoot {
+                stage,
+                merkle_root: "ab".repeat(32),
+                executors: vec!["executor1".to_string()],
+            };
+            execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        }
+
+        let search = |filter: RequestFilter, offset: Option<u64>, limit: u8, order: u8| {
+            let res = query_search_requests(
+                deps.as_ref(),
+                &mock_env(),
+                filter,
+                offset,
+                Some(limit),
+                Some(order),
+            )
+            .unwrap();
+            let stages: Vec<u64> = res.requests.iter().map(|request| request.stage).collect();
+            (stages, res.next_offset)
+        };
+        let filter = |service: Option<&str>, requester: Option<&str>| RequestFilter {
+            service: service.map(str::to_string),
+            requester: requester.map(str::to_string),
+            ..Default::default()
+        };
+
+        // all filters apply together
+        let mut requester1_a = filter(Some("a"), Some("requester1"));
+        assert_eq!(
+            search(requester1_a.clone(), None, 10, 1),
+            (vec![1, 5, 6], None)
+        );
+        requester1_a.finalized = Some(true);
+        assert_eq!(
+            search(requester1_a.clone(), None, 10, 1),
+            (vec![1, 5], None)
+        );
+        requester1_a.finalized = None;
+        requester1_a.status = Some(RequestStatus::Pending);
+        assert_eq!(search(requester1_a, None, 10, 1), (vec![6], None));
+        let mut heights = filter(None, None);
+        heights.min_height = Some(height + 2);
+        heights.max_height = Some(height + 4);
+        assert_eq!(
+            search(heights.clone(), None, 10, 1),
+            (vec![3, 4, 5, 6], None)
+        );
+        assert_eq!(
+            search(heights.clone(), None, 10, 2),
+            (vec![6, 5, 4, 3], None)
+        );
+        heights.service = Some("b".to_string());
+        assert_eq!(search(heights.clone(), None, 10, 1), (vec![4], None));
+        heights.min_height = Some(height + 7);
+        heights.max_height = None;
+        assert_eq!(search(heights, None, 10, 1), (vec![], None));
+
+        // the requester index reaches past the spam within one page
+        let requester1 = filter(None, Some("requester1"));
+        assert_eq!(
+            search(requester1.clone(), None, 10, 1),
+            (vec![1, 2, 5, 6, last_stage], None)
+        );
+        // pages continue from the offset in both orders
+        assert_eq!(
+            search(requester1.clone(), None, 2, 1),
+            (vec![1, 2], Some(2))
+        );
+        assert_eq!(
+            search(requester1.clone(), Some(2), 2, 1),
+            (vec![5, 6], Some(6))
+        );
+        assert_eq!(
+            search(requester1.clone(), Some(6), 2, 1),
+            (vec![last_stage], None)
+        );
+        assert_eq!(
+            search(requester1.clone(), None, 2, 2),
+            (vec![last_stage, 6], Some(6))
+        );
+        assert_eq!(search(requester1, Some(6), 2, 2), (vec![5, 2], Some(2)));
+
+        // without index the search stops after scanning MAX_SEARCH_SCAN requests
+        let finalized = RequestFilter {
+            finalized: Some(true),
+            ..Default::default()
+        };
+        let scan_end = MAX_SEARCH_SCAN as u64;
+        assert_eq!(
+            search(finalized.clone(), None, 10, 1),
+            (vec![1, 5], Some(scan_end))
+        );
+        assert_eq!(search(finalized, Some(scan_end), 10, 1), (vec![], None));
+    }
+
+    #[test]
+    fn list_queries_page_with_cursors_and_totals() {
+        let mut deps = mock_dependencies();
+        let msg = instantiate_msg(&["executor1", "executor2", "executor3"]);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        for service in ["service1", "service2", "service3"] {
+            add_service(deps.as_mut(), service, None);
+        }
+        for _ in 0..3 {
+            let msg = request_msg("service1", 1);
+            execute(deps.as_mut(), mock_env(), mock_info("requester", &[]), msg).unwrap();
+        }
+
+        let requests = |offset: Option<u64>| {
+            let msg = QueryMsg::GetRequests {
+                offset,
+                limit: Some(2),
+                order: Some(1),
+            };
+            let res: RequestListResponse =
+                from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+            let stages: Vec<u64> = res.items.iter().map(|request| request.stage).collect();
+            (stages, res.next_start_after, res.total)
+        };
+        assert_eq!(requests(None), (vec![1, 2], Some(2), Some(3)));
+        assert_eq!(requests(Some(2)), (vec![3], None, Some(3)));
+
+        let services = |start_after: Option<String>| {
+            let msg = QueryMsg::GetServices {
+                start: None,
+                end: None,
+                start_after,
+                order: Some(1),
+                limit: Some(2),
+            };
+            let res: ServiceListResponse =
+                from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+            let names: Vec<String> = res
+                .items
+                .into_iter()
+                .map(|service| service.service_name)
+                .collect();
+            (names, res.next_start_after, res.total)
+        };
+        let (names, next_start_after, total) = services(None);
+        assert_eq!(names, vec!["service1", "service2"]);
+        assert_eq!(
+            (next_start_after.as_deref(), total),
+            (Some("service2"), Some(3))
+        );
+        let (names, next_start_after, _) = services(next_start_after);
+        assert_eq!(
+            (names, next_start_after),
+            (vec!["service3".to_string()], None)
+        );
+
+        // executors are ordered by canonical address, the pages cover each of them once
+        let executors = |start_after: Option<String>| {
+            let msg = QueryMsg::GetExecutors {
+                start: None,
+                end: None,
+                start_after,
+                order: Some(1),
+                limit: Some(2),
+            };
+            let res: ExecutorListResponse =
+                from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+            let items: Vec<String> = res
+                .items
+                .into_iter()
+                .map(|executor| executor.executor)
+                .collect();
+            (items, res.next_start_after, res.total)
+        };
+        let (mut items, next_start_after, total) = executors(None);
+        assert_eq!((items.len(), total), (2, Some(3)));
+        assert_eq!(next_start_after.as_ref(), items.last());
+        let (rest, next_start_after, _) = executors(next_start_after);
+        assert_eq!(next_start_after, None);
+        items.extend(rest);
+        items.sort();
+        assert_eq!(items, vec!["executor1", "executor2", "executor3"]);
+    }
+
+    #[test]
+    fn requests_are_counted_per_requester() {
+        let mut deps = mock_dependencies();
+        let msg = instantiate_msg(&["executor1", "executor2", "executor3"]);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        add_service(deps.as_mut(), "service", None);
+        for requester in ["requester", "requester", "other"] {
+            let msg = request_msg("service", 1);
+            execute(deps.as_mut(), mock_env(), mock_info(requester, &[]), msg).unwrap();
+        }
+        let count =
+            |deps: Deps| query_requests_count_by_requester(deps, "requester".to_string()).unwrap();
+        assert_eq!(count(deps.as_ref()), 2);
+        let requests = query_requests_by_requester(
+            deps.as_ref(),
+            &mock_env(),
+            "requester".to_string(),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(requests.items.len(), 2);
+        assert_eq!(requests.total, Some(2));
+
+        // requests stored before the counter are counted by the request migration
+        requester_request_count_save(deps.as_mut().storage, &Addr::unchecked("requester"), 0)
+            .unwrap();
+        let migration = RequestMigration {
+            next_stage: 1,
+            last_stage: 3,
+        };
+        request_migration_save(deps.as_mut().storage, &migration).unwrap();
+        let msg = ExecuteMsg::MigrateRequests { limit: None };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        assert_eq!(count(deps.as_ref()), 2);
+    }
+
+    #[test]
+    fn queries_see_executor_changes_due_since_the_last_transaction() {
+        let mut deps = mock_dependencies();
+        let mut msg = instantiate_msg(&["executor1"]);
+        msg.epoch_length = Some(10);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        let msg = ExecuteMsg::UpdateConfig {
+            update_config_msg: UpdateConfigMsg {
+                new_executors: Some(vec!["executor2".to_string()]),
+                ..Default::default()
+            },
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+
+        let query_at = |deps: Deps, height: u64, msg: QueryMsg| {
+            let mut env = mock_env();
+            env.block.height = height;
+            query(deps, env, msg).unwrap()
+        };
+        let epoch_executors = |deps: Deps, height: u64| {
+            let msg = QueryMsg::GetCurrentEpochExecutors {
+                start_after: None,
+                limit: None,
+            };
+            let mut executors = from_binary::<EpochExecutorsResponse>(&query_at(deps, height, msg))
+                .unwrap()
+                .executors;
+            // listed by canonical address
+            executors.sort();
+            executors
+        };
+        let in_list = |deps: Deps, height: u64| {
+            let msg = QueryMsg::CheckExecutorInList {
+                address: "executor2".to_string(),
+            };
+            from_binary::<bool>(&query_at(deps, height, msg)).unwrap()
+        };
+        let size = |deps: Deps, height: u64| {
+            from_binary::<u64>(&query_at(deps, height, QueryMsg::GetExecutorSize {})).unwrap()
+        };
+        let executors = |deps: Deps, height: u64| {
+            let msg = QueryMsg::GetExecutors {
+                start: None,
+                end: None,
+                start_after: None,
+                order: Some(1),
+                limit: None,
+            };
+            let executors = from_binary::<ExecutorListResponse>(&query_at(deps, height, msg));
+            let executors = executors.unwrap();
+            let mut items: Vec<String> = executors
+                .items
+                .into_iter()
+                .map(|executor| executor.executor)
+                .collect();
+            items.sort();
+            (items, executors.total)
+        };
+
+        let height = mock_env().block.height;
+        assert_eq!(epoch_executors(deps.as_ref(), height), vec!["executor1"]);
+        assert!(!in_list(deps.as_ref(), height));
+        assert_eq!(size(deps.as_ref(), height), 1);
+        assert_eq!(
+            executors(deps.as_ref(), height),
+            (vec!["executor1".to_string()], Some(1))
+        );
+
+        let next_epoch = (height / 10 + 1) * 10;
+        assert_eq!(
+            epoch_executors(deps.as_ref(), next_epoch),
+            vec!["executor1", "executor2"]
+        );
+        assert!(in_list(deps.as_ref(), next_epoch));
+        let executor = query_executor(deps.as_ref(), next_epoch, "executor2".to_string()).unwrap();
+        assert_eq!(executor.joined_height, next_epoch);
+        assert!(query_executor(deps.as_ref(), height, "executor2".to_string()).is_err());
+        assert_eq!(size(deps.as_ref(), next_epoch), 2);
+        assert_eq!(
+            executors(deps.as_ref(), next_epoch),
+            (
+                vec!["executor1".to_string(), "executor2".to_string()],
+                Some(2)
+            )
+        );
+        // queries leave the changes queued for the next transaction
+        assert!(read_executor_changes(&deps.storage).unwrap().is_some());
+    }
+
+    #[test]
+    fn epoch_executors_leave_out_inactive_executors() {
+        let mut deps = mock_dependencies();
+        let mut msg = instantiate_msg(&["executor1", "executor2"]);
+        msg.min_executor_bond = Some(coin(100, "orai"));
+        msg.epoch_length = Some(10);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        let info = mock_info("executor1", &coins(100, "orai"));
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::BondExecutor {}).unwrap();
+        let msg = ExecuteMsg::UpdateConfig {
+            update_config_msg: UpdateConfigMsg {
+                new_executors: Some(vec!["executor3".to_string()]),
+                ..Default::default()
+            },
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+
+        for next in [false, true] {
+            let executors =
+                query_epoch_executors(deps.as_ref(), mock_env().block.height, next, None, None)
+                    .unwrap();
+            assert_eq!(executors.executors, vec!["executor1"]);
+        }
+    }
+
+    #[test]
+    fn unbonding_outlasts_challenges() {
+        let mut deps = mock_dependencies();
+        let mut msg = instantiate_msg(&[]);
+        msg.challenge_period = Some(10);
+        msg.unbonding_period = Some(5);
+        let err = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::InvalidUnbondingPeriod {});
+        msg.unbonding_period = Some(10);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+
+        let update_config = |new_challenge_period, new_unbonding_period| ExecuteMsg::UpdateConfig {
+            update_config_msg: UpdateConfigMsg {
+                new_challenge_period,
+                new_unbonding_period,
+                ..Default::default()
+            },
+        };
+        for msg in [update_config(Some(20), None), update_config(None, Some(5))] {
+            let err = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap_err();
+            assert_eq!(err, ContractError::InvalidUnbondingPeriod {});
+        }
+        let msg = update_config(Some(20), Some(30));
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn merkle_roots_keep_the_challenge_period_they_were_registered_with() {
+        let mut deps = mock_dependencies();
+        let mut msg = instantiate_msg(&["executor1", "executor2", "executor3"]);
+        msg.challenge_period = Some(10);
+        msg.unbonding_period = Some(10);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        add_service(deps.as_mut(), "service", None);
+        let height = mock_env().block.height;
+        let env_at = |height: u64| {
+            let mut env = mock_env();
+            env.block.height = height;
+            env
+        };
+        let register = |deps: DepsMut, stage: u64, height: u64, executor: &str| {
+            let msg = ExecuteMsg::RegisterMerkleRoot {
+                stage,
+                merkle_root: "ab".repeat(32),
+                executors: vec![executor.to_string()],
+            };
+            execute(deps, env_at(height), mock_info("owner", &[]), msg).unwrap();
+        };
+        for _ in 0..2 {
+            let msg = request_msg("service", 1);
+            execute(deps.as_mut(), mock_env(), mock_info("requester", &[]), msg).unwrap();
+        }
+        register(deps.as_mut(), 1, height, "executor1");
+        let msg = ExecuteMsg::UpdateConfig {
+            update_config_msg: UpdateConfigMsg {
+                new_challenge_period: Some(2),
+                ..Default::default()
+            },
+        };
+        execute(
+            deps.as_mut(),
+            env_at(height + 1),
+            mock_info("owner", &[]),
+            msg,
+        )
+        .unwrap();
+        register(deps.as_mut(), 2, height + 1, "executor1");
+        let request = query_request(deps.as_ref(), &mock_env(), 1).unwrap();
+        assert_eq!(request.challenge_end_height, Some(height + 10));
+
+        // the root of stage 1 stays open to challenges for the period it was registered with
+        let verify = |deps: Deps, stage: u64, height: u64| {
+            let response = verify_data(
+                deps,
+                env_at(height),
+                stage,
+                Binary::from(b"data".as_slice()),
+                None,
+            )
+            .unwrap();
+            (response.finalized, response.challengeable)
+        };
+        assert_eq!(verify(deps.as_ref(), 1, height + 5), (false, true));
+        assert_eq!(verify(deps.as_ref(), 2, height + 5), (true, false));
+        let filter = RequestFilter {
+            finalized: Some(true),
+            ..Default::default()
+        };
+        let res =
+            query_search_requests(deps.as_ref(), &env_at(height + 5), filter, None, None, None);
+        let stages: Vec<u64> = res
+            .unwrap()
+            .requests
+            .iter()
+            .map(|request| request.stage)
+            .collect();
+        assert_eq!(stages, vec![2]);
+        let msg = ExecuteMsg::FinalizeMerkleRoot { stage: 1 };
+        let err = execute(
+            deps.as_mut(),
+            env_at(height + 5),
+            mock_info("anyone", &[]),
+            msg,
+        );
+        assert_eq!(err.unwrap_err(), ContractError::ChallengePeriodNotEnded {});
+        let msg = ExecuteMsg::ChallengeMerkleRoot {
+            stage: 1,
+            evidence: "evidence".to_string(),
+        };
+        execute(
+            deps.as_mut(),
+            env_at(height + 5),
+            mock_info("executor1", &[]),
+            msg,
+        )
+        .unwrap();
+
+        // an upheld challenge clears the window until a new root is registered
+        let msg = ExecuteMsg::ResolveChallenge {
+            stage: 1,
+            upheld: true,
+        };
+        execute(
+            deps.as_mut(),
+            env_at(height + 6),
+            mock_info("owner", &[]),
+            msg,
+        )
+        .unwrap();
+        let request = query_request(deps.as_ref(), &mock_env(), 1).unwrap();
+        assert_eq!(request.challenge_end_height, None);
+        // executor1 got jailed by the challenge
+        register(deps.as_mut(), 1, height + 20, "executor3");
+        let msg = ExecuteMsg::FinalizeMerkleRoot { stage: 1 };
+        execute(
+            deps.as_mut(),
+            env_at(height + 22),
+            mock_info("anyone", &[]),
+            msg,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn unbonded_funds_wait_for_open_challenges() {
+        let mut deps = mock_dependencies();
+        let mut msg = instantiate_msg(&["executor1", "executor2", "executor3"]);
+        msg.min_executor_bond = Some(coin(100, "orai"));
+        msg.challenge_period = Some(5);
+        msg.unbonding_period = Some(5);
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        for executor in ["executor1", "executor2", "executor3"] {
+            let info = mock_info(executor, &coins(100, "orai"));
+            execute(deps.as_mut(), mock_env(), info, ExecuteMsg::BondExecutor {}).unwrap();
+        }
+        add_service(deps.as_mut(), "service", None);
+        let msg = request_msg("service", 1);
+        execute(deps.as_mut(), mock_env(), mock_info("requester", &[]), msg).unwrap();
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            stage: 1,
+            merkle_root: "ab".repeat(32),
+            executors: vec!["executor1".to_string()],
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        let msg = ExecuteMsg::UnbondExecutor {
+            amount: Uint128::new(100),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("executor1", &[]), msg).unwrap();
+        let msg = ExecuteMsg::ChallengeMerkleRoot {
+            stage: 1,
+            evidence: "wrong result".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("executor2", &[]), msg).unwrap();
+
+        let mut env = mock_env();
+        env.block.height += 5;
+        let info = mock_info("executor1", &[]);
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::WithdrawUnbonded {},
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::OpenChallenges {});
+
+        let msg = ExecuteMsg::ResolveChallenge {
+            stage: 1,
+            upheld: false,
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), msg).unwrap();
+        let res = execute(deps.as_mut(), env, info, ExecuteMsg::WithdrawUnbonded {}).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "executor1".to_string(),
+                amount: coins(100, "orai"),
+            }
+            .into()
+        );
+    }
+
+    #[test]
+    fn challenges_put_up_a_deposit() {
+        let mut deps = mock_dependencies();
+        let mut msg = instantiate_msg(&["executor1", "executor2", "executor3"]);
+        msg.challenge_period = Some(10);
+        msg.unbonding_period = Some(10);
+        msg.challenge_deposit = Some(coin(100, "orai"));
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        add_service(deps.as_mut(), "service", None);
+        for stage in 1..=2 {
+            let msg = request_msg("service", 1);
+            execute(deps.as_mut(), mock_env(), mock_info("requester", &[]), msg).unwrap();
+            let msg = ExecuteMsg::RegisterMerkleRoot {
+                stage,
+                merkle_root: "ab".repeat(32),
+                executors: vec!["executor1".to_string()],
+            };
+            execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        }
+        let challenge = |stage| ExecuteMsg::ChallengeMerkleRoot {
+            stage,
+            evidence: "wrong result".to_string(),
+        };
+
+        let info = mock_info("executor2", &coins(50, "orai"));
+        let err = execute(deps.as_mut(), mock_env(), info, challenge(1)).unwrap_err();
+        assert_eq!(err, ContractError::InsufficientFunds {});
+        let info = mock_info("executor2", &coins(150, "orai"));
+        let res = execute(deps.as_mut(), mock_env(), info, challenge(1)).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "executor2".to_string(),
+                amount: coins(50, "orai"),
+            }
+            .into()
+        );
+
+        // a rejected challenge loses its deposit to the executors of the root
+        let msg = ExecuteMsg::ResolveChallenge {
+            stage: 1,
+            upheld: false,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        let executor1 = deps.api.addr_canonicalize("executor1").unwrap();
+        assert_eq!(
+            read_executor_rewards(&deps.storage, &executor1),
+            coins(100, "orai")
+        );
+
+        // an upheld challenge gets its deposit back
+        let info = mock_info("executor2", &coins(100, "orai"));
+        execute(deps.as_mut(), mock_env(), info, challenge(2)).unwrap();
+        let msg = ExecuteMsg::ResolveChallenge {
+            stage: 2,
+            upheld: true,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "executor2".to_string(),
+                amount: coins(100, "orai"),
+            }
+            .into()
+        );
+    }
 }
@@ -32,6 +32,54 @@ pub enum ContractError {
     #[error("Request already finished")]
     AlreadyFinished {},
 
+    #[error("Request expired")]
+    RequestExpired {},
+
+    #[error("Request not expired yet")]
+    RequestNotExpired {},
+
+    #[error("Invalid request status for this action")]
+    InvalidRequestStatus {},
+
+    #[error("Challenge period has ended")]
+    ChallengePeriodEnded {},
+
+    #[error("Challenge period has not ended yet")]
+    ChallengePeriodNotEnded {},
+
+    #[error("Executor bonding is not enabled")]
+    BondingDisabled {},
+
+    #[error("Slash fraction must not exceed one")]
+    InvalidSlashFraction {},
+
+    #[error("Unbonding period must not be shorter than the challenge period")]
+    InvalidUnbondingPeriod {},
+
+    #[error("Cannot change the bond denom while executors have bonds")]
+    BondDenomLocked {},
+
+    #[error("Executor is not jailed")]
+    NotJailed {},
+
+    #[error("Executor is still jailed")]
+    StillJailed {},
+
+    #[error("Executor has open challenges against its merkle roots")]
+    OpenChallenges {},
+
+    #[error("Nothing to withdraw")]
+    NothingToWithdraw {},
+
+    #[error("No reward to claim")]
+    NoReward {},
+
+    #[error("Already an executor")]
+    AlreadyExecutor {},
+
+    #[error("Executor application not found")]
+    ApplicationNotFound {},
+
     #[error("Service not found")]
     ServiceNotFound {},
 
@@ -41,6 +89,15 @@ pub enum ContractError {
     #[error("Verification failed")]
     VerificationFailed {},
 
+    #[error("Result exceeds {max_size} bytes")]
+    ResultTooLarge { max_size: usize },
+
+    #[error("Stage already holds {max_results} results")]
+    TooManyResults { max_results: usize },
+
+    #[error("No requests left to migrate")]
+    NothingToMigrate {},
+
     #[error("Cannot migrate from different contract type: {previous_contract}")]
     CannotMigrate { previous_contract: String },
 }
@@ -1,49 +1,685 @@
-use cosmwasm_std::{CanonicalAddr, Deps, Order, StdResult, Storage};
+use cosmwasm_std::{
+    from_slice, Addr, Api, Binary, CanonicalAddr, Coin, Deps, Event, Order, StdError, StdResult,
+    Storage, Uint128,
+};
+use sha2::Digest;
 
+use crate::msg::{
+    EpochExecutorsResponse, ExecutorApplicationListResponse, ExecutorApplicationResponse,
+    ExecutorBondResponse, ExecutorListResponse, ExecutorRewardsResponse, ExecutorsResponse,
+};
 use crate::state::{
-    executor_prefixes, match_order, remove_executor, store_executor, DEFAULT_LIMIT,
+    active_executor_count_read, config_read, epoch_at, epoch_start_height, executor_count_read,
+    executor_prefixes, match_order, open_challenge_count_read, open_challenge_count_save,
+    range_after, read_executor, read_executor_application, read_executor_applications,
+    read_executor_bond, read_executor_changes, read_executor_rewards, remove_executor,
+    remove_executor_application, remove_executor_changes, store_executor, store_executor_bond,
+    store_executor_changes, store_executor_rewards, Executor, ExecutorApplication, ExecutorBond,
+    ExecutorChanges, DEFAULT_LIMIT, MAX_LIMIT,
 };
+use crate::ContractError;
+
+/// Adds new executors, executors already in the list keep their record. New executors take
+/// the key and metadata of their application if they applied.
+pub fn store_executors(
+    storage: &mut dyn Storage,
+    executors: Vec<CanonicalAddr>,
+    height: u64,
+) -> StdResult<()> {
+    for executor in executors {
+        if read_executor(storage, &executor).is_none() {
+            let executor_info = joining_executor(storage, &executor, height)?;
+            store_executor(storage, &executor, &executor_info)?;
+        }
+        remove_executor_application(storage, &executor);
+    }
+    Ok(())
+}
+
+/// Record of an executor joining at the height, active if its bond is enough.
+fn joining_executor(
+    storage: &dyn Storage,
+    executor: &CanonicalAddr,
+    height: u64,
+) -> StdResult<Executor> {
+    let application = read_executor_application(storage, executor);
+    let min_executor_bond = config_read(storage)?.min_executor_bond;
+    Ok(Executor {
+        pubkey: application
+            .as_ref()
+            .map(|application| application.pubkey.clone()),
+        is_active: is_sufficiently_bonded(
+            min_executor_bond.as_ref(),
+            &read_executor_bond(storage, executor),
+        ),
+        joined_height: height,
+        metadata: application.and_then(|application| application.metadata),
+        jailed_until: None,
+    })
+}
+
+/// Whether the bond is enough for the executor to take part in requests.
+pub fn is_sufficiently_bonded(min_executor_bond: Option<&Coin>, bond: &ExecutorBond) -> bool {
+    min_executor_bond.is_none_or(|min_executor_bond| {
+        min_executor_bond.amount.is_zero()
+            || (bond.denom == min_executor_bond.denom && bond.amount >= min_executor_bond.amount)
+    })
+}
+
+/// Recomputes whether the executor is active after its bond, the bond requirement
+/// or its jail changed.
+pub fn update_executor_status(
+    storage: &mut dyn Storage,
+    executor: &CanonicalAddr,
+) -> StdResult<()> {
+    let Some(mut executor_info) = read_executor(storage, executor) else {
+        return Ok(());
+    };
+    let min_executor_bond = config_read(storage)?.min_executor_bond;
+    let is_active = executor_info.jailed_until.is_none()
+        && is_sufficiently_bonded(
+            min_executor_bond.as_ref(),
+            &read_executor_bond(storage, executor),
+        );
+    if executor_info.is_active != is_active {
+        executor_info.is_active = is_active;
+        store_executor(storage, executor, &executor_info)?;
+    }
+    Ok(())
+}
 
-pub fn store_executors(storage: &mut dyn Storage, executors: Vec<CanonicalAddr>) {
+/// Recomputes the status of every executor, used when the bond requirement changes.
+pub fn update_executors_status(storage: &mut dyn Storage) -> StdResult<()> {
+    let executors: Vec<CanonicalAddr> = executor_prefixes(storage)
+        .range(None, None, Order::Ascending)
+        .map(|(executor, _)| CanonicalAddr::from(executor))
+        .collect();
     for executor in executors {
-        store_executor(storage, executor)
+        update_executor_status(storage, &executor)?;
+    }
+    Ok(())
+}
+
+pub fn remove_executors(storage: &mut dyn Storage, executors: Vec<CanonicalAddr>) -> StdResult<()> {
+    for executor in executors {
+        remove_executor(storage, &executor)?;
+    }
+    Ok(())
+}
+
+/// Queues executor changes for the start of the next epoch, the queue is applied right away when
+/// epochs are disabled. A later change of the same executor replaces the queued one, removed
+/// executors lose their application.
+pub fn queue_executor_changes(
+    storage: &mut dyn Storage,
+    new_executors: Vec<CanonicalAddr>,
+    old_executors: Vec<CanonicalAddr>,
+    height: u64,
+) -> StdResult<()> {
+    for executor in &old_executors {
+        remove_executor_application(storage, executor);
+    }
+    let epoch_length = config_read(storage)?.epoch_length;
+    let apply_height = if epoch_length == 0 {
+        height
+    } else {
+        epoch_start_height(epoch_length, epoch_at(epoch_length, height) + 1)
+    };
+    let mut changes = read_executor_changes(storage)?.unwrap_or(ExecutorChanges {
+        new_executors: vec![],
+        old_executors: vec![],
+        apply_height,
+    });
+    changes.apply_height = apply_height;
+    for executor in new_executors {
+        changes.old_executors.retain(|old| old != &executor);
+        if !changes.new_executors.contains(&executor) {
+            changes.new_executors.push(executor);
+        }
+    }
+    for executor in old_executors {
+        changes.new_executors.retain(|new| new != &executor);
+        if !changes.old_executors.contains(&executor) {
+            changes.old_executors.push(executor);
+        }
+    }
+    store_executor_changes(storage, &changes)?;
+    // without epochs the changes, along with any left queued, apply right away
+    apply_executor_changes(storage, height)
+}
+
+/// Whether queued changes apply at the height, always once epochs have been disabled.
+fn are_changes_due(changes: &ExecutorChanges, epoch_length: u64, height: u64) -> bool {
+    epoch_length == 0 || changes.apply_height <= height
+}
+
+/// Applies the queued executor changes once their epoch has started, or at once if epochs have
+/// been disabled since. Called before every execution, so the first transaction of an epoch
+/// brings the executor set up to date.
+pub fn apply_executor_changes(storage: &mut dyn Storage, height: u64) -> StdResult<()> {
+    let Some(changes) = read_executor_changes(storage)? else {
+        return Ok(());
+    };
+    if !are_changes_due(&changes, config_read(storage)?.epoch_length, height) {
+        return Ok(());
+    }
+    remove_executor_changes(storage);
+    remove_executors(storage, changes.old_executors)?;
+    store_executors(storage, changes.new_executors, height)
+}
+
+/// Executors as a transaction at the height would find them, the queued changes due by then
+/// applied in memory. Executor queries read through it, so they show the set of a new epoch
+/// before its first transaction stores it.
+pub struct ExecutorView<'a> {
+    storage: &'a dyn Storage,
+    changes: ExecutorChanges,
+    height: u64,
+}
+
+impl<'a> ExecutorView<'a> {
+    pub fn new(storage: &'a dyn Storage, height: u64) -> StdResult<Self> {
+        Self::with_changes(storage, height, false)
+    }
+
+    /// Executors of the next epoch, with all queued changes applied.
+    pub fn next_epoch(storage: &'a dyn Storage, height: u64) -> StdResult<Self> {
+        Self::with_changes(storage, height, true)
+    }
+
+    fn with_changes(storage: &'a dyn Storage, height: u64, all: bool) -> StdResult<Self> {
+        let epoch_length = config_read(storage)?.epoch_length;
+        let changes = read_executor_changes(storage)?
+            .filter(|changes| all || are_changes_due(changes, epoch_length, height))
+            .unwrap_or(ExecutorChanges {
+                new_executors: vec![],
+                old_executors: vec![],
+                apply_height: height,
+            });
+        Ok(ExecutorView {
+            storage,
+            changes,
+            height,
+        })
+    }
+
+    fn is_joining(&self, executor: &CanonicalAddr) -> bool {
+        self.changes.new_executors.contains(executor)
+            && read_executor(self.storage, executor).is_none()
+    }
+
+    pub fn executor(&self, executor: &CanonicalAddr) -> StdResult<Option<Executor>> {
+        if self.changes.old_executors.contains(executor) {
+            return Ok(None);
+        }
+        if self.is_joining(executor) {
+            return joining_executor(self.storage, executor, self.height).map(Some);
+        }
+        Ok(read_executor(self.storage, executor))
+    }
+
+    /// Executors within the key range kept by the filter, in the given order.
+    pub fn executors(
+        &self,
+        (start, end): (Option<&[u8]>, Option<&[u8]>),
+        order: Order,
+        limit: usize,
+        filter: impl Fn(&Executor) -> bool,
+    ) -> StdResult<Vec<(CanonicalAddr, Executor)>> {
+        // the first executors of both the stored and the joining ones make the page
+        let mut executors = executor_prefixes(self.storage)
+            .range(start, end, order)
+            .map(|(executor, executor_info)| {
+                Ok((CanonicalAddr::from(executor), from_slice(&executor_info)?))
+            })
+            .filter(|executor| {
+                executor.as_ref().map_or(true, |(executor, executor_info)| {
+                    !self.changes.old_executors.contains(executor) && filter(executor_info)
+                })
+            })
+            .take(limit)
+            .collect::<StdResult<Vec<(CanonicalAddr, Executor)>>>()?;
+        for executor in &self.changes.new_executors {
+            let in_range = start.is_none_or(|start| executor.as_slice() >= start)
+                && end.is_none_or(|end| executor.as_slice() < end);
+            if !in_range || !self.is_joining(executor) {
+                continue;
+            }
+            let executor_info = joining_executor(self.storage, executor, self.height)?;
+            if filter(&executor_info) {
+                executors.push((executor.clone(), executor_info));
+            }
+        }
+        executors.sort_by(|(a, _), (b, _)| match order {
+            Order::Ascending => a.as_slice().cmp(b.as_slice()),
+            Order::Descending => b.as_slice().cmp(a.as_slice()),
+        });
+        executors.truncate(limit);
+        Ok(executors)
+    }
+
+    /// Number of executors and of active executors.
+    pub fn counts(&self) -> StdResult<(u64, u64)> {
+        let mut total = executor_count_read(self.storage);
+        let mut active = active_executor_count_read(self.storage);
+        for executor in &self.changes.old_executors {
+            if let Some(executor_info) = read_executor(self.storage, executor) {
+                total = total.saturating_sub(1);
+                active = active.saturating_sub(executor_info.is_active as u64);
+            }
+        }
+        for executor in &self.changes.new_executors {
+            if self.is_joining(executor) {
+                total += 1;
+                active += joining_executor(self.storage, executor, self.height)?.is_active as u64;
+            }
+        }
+        Ok((total, active))
+    }
+}
+
+/// Executors used to be stored as a single marker byte, this turns them into full records.
+pub fn migrate_executors(storage: &mut dyn Storage, height: u64) -> StdResult<()> {
+    let legacy_executors: Vec<Vec<u8>> = executor_prefixes(storage)
+        .range(None, None, Order::Ascending)
+        .filter(|(_, value)| value.as_slice() == [0])
+        .map(|(executor, _)| executor)
+        .collect();
+    store_executors(
+        storage,
+        legacy_executors
+            .into_iter()
+            .map(CanonicalAddr::from)
+            .collect(),
+        height,
+    )
+}
+
+/// Takes the slash fraction of the bond, unbonding funds included, of each executor and jails
+/// them. Returns the total slashed funds in the denoms they were bonded in and a slash event
+/// per executor.
+pub fn slash_executors(
+    storage: &mut dyn Storage,
+    api: &dyn Api,
+    executors: &[Addr],
+    height: u64,
+) -> StdResult<(Vec<Coin>, Vec<Event>)> {
+    let config = config_read(storage)?;
+    let mut total: Vec<Coin> = vec![];
+    let mut events = vec![];
+    for executor_addr in executors {
+        let executor = &api.addr_canonicalize(executor_addr.as_str())?;
+        let mut bond = read_executor_bond(storage, executor);
+        let mut slashed: Vec<Coin> = vec![];
+        let bond_slashed = bond.amount * config.slash_fraction;
+        bond.amount = bond.amount.checked_sub(bond_slashed)?;
+        add_coin(&mut slashed, &bond.denom, bond_slashed);
+        for unbonding in bond.unbonding.iter_mut() {
+            let unbonding_slashed = unbonding.amount * config.slash_fraction;
+            unbonding.amount = unbonding.amount.checked_sub(unbonding_slashed)?;
+            add_coin(&mut slashed, &unbonding.denom, unbonding_slashed);
+        }
+        bond.unbonding
+            .retain(|unbonding| !unbonding.amount.is_zero());
+        store_executor_bond(storage, executor, &bond)?;
+        for coin in &slashed {
+            add_coin(&mut total, &coin.denom, coin.amount);
+        }
+
+        let jailed_until = height.saturating_add(config.jail_period);
+        if let Some(mut executor_info) = read_executor(storage, executor) {
+            executor_info.jailed_until = Some(jailed_until);
+            store_executor(storage, executor, &executor_info)?;
+            update_executor_status(storage, executor)?;
+        }
+        events.push(
+            Event::new("slash")
+                .add_attribute("executor", executor_addr)
+                .add_attribute(
+                    "amount",
+                    slashed
+                        .iter()
+                        .map(Coin::to_string)
+                        .collect::<Vec<String>>()
+                        .join(","),
+                )
+                .add_attribute("jailed_until", jailed_until.to_string()),
+        );
     }
+    Ok((total, events))
 }
 
-pub fn remove_executors(storage: &mut dyn Storage, executors: Vec<CanonicalAddr>) {
+/// Counts a challenge opened or resolved against the merkle root of the executors, their
+/// unbonded funds stay in the contract while a challenge against them is open.
+pub fn update_open_challenges(
+    storage: &mut dyn Storage,
+    api: &dyn Api,
+    executors: &[Addr],
+    opened: bool,
+) -> StdResult<()> {
     for executor in executors {
-        remove_executor(storage, executor)
+        let executor = api.addr_canonicalize(executor.as_str())?;
+        let count = open_challenge_count_read(storage, &executor);
+        let count = if opened {
+            count.saturating_add(1)
+        } else {
+            count.saturating_sub(1)
+        };
+        open_challenge_count_save(storage, &executor, count)?;
+    }
+    Ok(())
+}
+
+/// Adds the amount to the coin of the same denom, zero amounts are left out.
+pub fn add_coin(coins: &mut Vec<Coin>, denom: &str, amount: Uint128) {
+    if amount.is_zero() {
+        return;
+    }
+    match coins.iter_mut().find(|coin| coin.denom == denom) {
+        Some(coin) => coin.amount += amount,
+        None => coins.push(Coin {
+            denom: denom.to_string(),
+            amount,
+        }),
+    }
+}
+
+/// Splits the fees evenly between the executors and adds the shares to their reward ledgers.
+/// The remainder of the division goes to the first executor so nothing is left unaccounted.
+pub fn distribute_rewards(
+    storage: &mut dyn Storage,
+    executors: &[CanonicalAddr],
+    fees: &[Coin],
+) -> StdResult<()> {
+    if executors.is_empty() {
+        return Ok(());
+    }
+    let count = Uint128::from(executors.len() as u128);
+    for (index, executor) in executors.iter().enumerate() {
+        let mut rewards = read_executor_rewards(storage, executor);
+        for fee in fees {
+            let mut share = fee.amount.checked_div(count)?;
+            if index == 0 {
+                share += fee.amount.checked_rem(count)?;
+            }
+            if share.is_zero() {
+                continue;
+            }
+            match rewards.iter_mut().find(|reward| reward.denom == fee.denom) {
+                Some(reward) => reward.amount += share,
+                None => rewards.push(Coin {
+                    denom: fee.denom.clone(),
+                    amount: share,
+                }),
+            }
+        }
+        store_executor_rewards(storage, executor, &rewards)?;
+    }
+    Ok(())
+}
+
+/// Hash executors sign to attest a merkle root, bound to this contract and stage.
+pub fn merkle_root_signing_hash(contract: &Addr, stage: u64, merkle_root: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(contract.as_bytes());
+    hasher.update(stage.to_be_bytes());
+    hasher.update(merkle_root);
+    hasher.finalize().into()
+}
+
+/// Hash a new executor key signs to prove it is held by the executor rotating to it.
+pub fn executor_key_signing_hash(contract: &Addr, executor: &Addr, pubkey: &[u8]) -> [u8; 32] {
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(contract.as_bytes());
+    hasher.update(executor.as_bytes());
+    hasher.update(pubkey);
+    hasher.finalize().into()
+}
+
+/// Checks an executor signature against the registered key of the executor. Without registered
+/// key the given public key is used and must belong to the executor account.
+pub fn verify_executor_signature(
+    api: &dyn Api,
+    executor: &CanonicalAddr,
+    executor_info: &Executor,
+    message_hash: &[u8; 32],
+    signature: &[u8],
+    pubkey: Option<&Binary>,
+) -> Result<(), ContractError> {
+    let pubkey = match (&executor_info.pubkey, pubkey) {
+        (Some(registered), Some(pubkey)) if registered != pubkey => {
+            return Err(ContractError::InvalidSignature {})
+        }
+        (Some(registered), _) => registered,
+        (None, Some(pubkey)) => {
+            if pubkey.len() != 33 {
+                return Err(ContractError::WrongLength {});
+            }
+            // account addresses are ripemd160(sha256(pubkey))
+            let pubkey_hash = sha2::Sha256::digest(pubkey.as_slice());
+            let address = <ripemd::Ripemd160 as ripemd::Digest>::digest(pubkey_hash);
+            if address.as_slice() != executor.as_slice() {
+                return Err(ContractError::InvalidSignature {});
+            }
+            pubkey
+        }
+        (None, None) => return Err(ContractError::InvalidSignature {}),
+    };
+    verify_signature(api, message_hash, signature, pubkey)
+}
+
+pub fn verify_signature(
+    api: &dyn Api,
+    message_hash: &[u8; 32],
+    signature: &[u8],
+    pubkey: &[u8],
+) -> Result<(), ContractError> {
+    if !api
+        .secp256k1_verify(message_hash, signature, pubkey)
+        .map_err(|_| ContractError::VerificationFailed {})?
+    {
+        return Err(ContractError::VerificationFailed {});
     }
+    Ok(())
 }
 
 // query functions
 
+fn to_executor_response(
+    api: &dyn Api,
+    executor: &CanonicalAddr,
+    executor_info: Executor,
+) -> StdResult<ExecutorsResponse> {
+    Ok(ExecutorsResponse {
+        executor: api.addr_humanize(executor)?.to_string(),
+        pubkey: executor_info.pubkey,
+        is_active: executor_info.is_active,
+        joined_height: executor_info.joined_height,
+        metadata: executor_info.metadata,
+        jailed_until: executor_info.jailed_until,
+    })
+}
+
 pub fn query_executors(
     deps: Deps,
+    height: u64,
     start: Option<String>,
     end: Option<String>,
+    start_after: Option<String>,
     order: Option<u8>,
     limit: Option<u8>,
-) -> StdResult<Vec<String>> {
-    let executor_prefixes = executor_prefixes(deps.storage);
-    let executors: StdResult<Vec<String>> = executor_prefixes
-        .range(
-            start.map(|start| String::into_bytes(start)).as_deref(),
-            end.map(|start| String::into_bytes(start)).as_deref(),
+) -> StdResult<ExecutorListResponse> {
+    let start_after = start_after
+        .map(|executor| deps.api.addr_canonicalize(&executor).map(Vec::from))
+        .transpose()?;
+    let (start, end) = range_after(
+        start.map(String::into_bytes),
+        end.map(String::into_bytes),
+        start_after,
+        match_order(order),
+    );
+    let view = ExecutorView::new(deps.storage, height)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT) as usize;
+    let items = view
+        .executors(
+            (start.as_deref(), end.as_deref()),
             match_order(order),
-        )
-        .take(limit.unwrap_or(DEFAULT_LIMIT) as usize)
-        .map(|executor| {
-            deps.api
-                .addr_humanize(&CanonicalAddr::from(executor.0))
-                .map(|executor| executor.to_string())
+            limit,
+            |_| true,
+        )?
+        .into_iter()
+        .map(|(executor, executor_info)| to_executor_response(deps.api, &executor, executor_info))
+        .collect::<StdResult<Vec<ExecutorsResponse>>>()?;
+    let next_start_after = items
+        .last()
+        .filter(|_| items.len() == limit)
+        .map(|executor| executor.executor.clone());
+    Ok(ExecutorListResponse {
+        items,
+        next_start_after,
+        total: Some(view.counts()?.0),
+    })
+}
+
+pub fn query_executor(deps: Deps, height: u64, executor: String) -> StdResult<ExecutorsResponse> {
+    let executor = deps.api.addr_canonicalize(&executor)?;
+    let executor_info = ExecutorView::new(deps.storage, height)?
+        .executor(&executor)?
+        .ok_or_else(|| StdError::not_found("executor"))?;
+    to_executor_response(deps.api, &executor, executor_info)
+}
+
+/// Whether the executor takes part in requests.
+pub fn query_executor_in_list(deps: Deps, height: u64, executor: String) -> StdResult<bool> {
+    let executor = deps.api.addr_canonicalize(&executor)?;
+    Ok(ExecutorView::new(deps.storage, height)?
+        .executor(&executor)?
+        .is_some_and(|executor| executor.is_active))
+}
+
+/// Active executors of the current epoch, or of the next one with all queued changes applied.
+pub fn query_epoch_executors(
+    deps: Deps,
+    height: u64,
+    next: bool,
+    start_after: Option<String>,
+    limit: Option<u8>,
+) -> StdResult<EpochExecutorsResponse> {
+    let epoch_length = config_read(deps.storage)?.epoch_length;
+    let mut epoch = epoch_at(epoch_length, height);
+    if next && epoch_length > 0 {
+        epoch += 1;
+    }
+    let view = if next {
+        ExecutorView::next_epoch(deps.storage, height)?
+    } else {
+        ExecutorView::new(deps.storage, height)?
+    };
+
+    let start_after = start_after
+        .map(|executor| deps.api.addr_canonicalize(&executor).map(Vec::from))
+        .transpose()?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let (start, _) = range_after(None, None, start_after, Order::Ascending);
+    let mut executors = view.executors(
+        (start.as_deref(), None),
+        Order::Ascending,
+        limit + 1,
+        |executor_info| executor_info.is_active,
+    )?;
+    let has_more = executors.len() > limit;
+    executors.truncate(limit);
+
+    let executors = executors
+        .iter()
+        .map(|(executor, _)| Ok(deps.api.addr_humanize(executor)?.to_string()))
+        .collect::<StdResult<Vec<String>>>()?;
+    Ok(EpochExecutorsResponse {
+        epoch,
+        start_height: epoch_start_height(epoch_length, epoch),
+        next_start_after: executors.last().filter(|_| has_more).cloned(),
+        executors,
+    })
+}
+
+/// Number of executors taking part in requests.
+pub fn query_executor_size(deps: Deps, height: u64) -> StdResult<u64> {
+    Ok(ExecutorView::new(deps.storage, height)?.counts()?.1)
+}
+
+/// Number of the given executors taking part in requests.
+pub fn query_allowed_executor_size(deps: Deps, executors: &[Addr]) -> StdResult<u64> {
+    let mut size = 0;
+    for executor in executors {
+        let executor = deps.api.addr_canonicalize(executor.as_str())?;
+        if read_executor(deps.storage, &executor).is_some_and(|executor| executor.is_active) {
+            size += 1;
+        }
+    }
+    Ok(size)
+}
+
+fn to_executor_application_response(
+    api: &dyn Api,
+    applicant: &CanonicalAddr,
+    application: ExecutorApplication,
+) -> StdResult<ExecutorApplicationResponse> {
+    Ok(ExecutorApplicationResponse {
+        applicant: api.addr_humanize(applicant)?.to_string(),
+        pubkey: application.pubkey,
+        metadata: application.metadata,
+        height: application.height,
+        approved: application.approved,
+    })
+}
+
+pub fn query_executor_application(
+    deps: Deps,
+    applicant: String,
+) -> StdResult<ExecutorApplicationResponse> {
+    let applicant = deps.api.addr_canonicalize(&applicant)?;
+    let application = read_executor_application(deps.storage, &applicant)
+        .ok_or_else(|| StdError::not_found("executor application"))?;
+    to_executor_application_response(deps.api, &applicant, application)
+}
+
+pub fn query_executor_applications(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u8>,
+) -> StdResult<ExecutorApplicationListResponse> {
+    let start_after = start_after
+        .map(|applicant| deps.api.addr_canonicalize(&applicant).map(Vec::from))
+        .transpose()?;
+    let (start, _) = range_after(None, None, start_after, Order::Ascending);
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let items = read_executor_applications(deps.storage, start.as_deref(), limit)?
+        .into_iter()
+        .map(|(applicant, application)| {
+            to_executor_application_response(deps.api, &applicant, application)
         })
-        .collect();
-    Ok(executors?)
+        .collect::<StdResult<Vec<ExecutorApplicationResponse>>>()?;
+    let next_start_after = items
+        .last()
+        .filter(|_| items.len() == limit)
+        .map(|application| application.applicant.clone());
+    Ok(ExecutorApplicationListResponse {
+        items,
+        next_start_after,
+    })
 }
 
-pub fn query_executor_size(deps: Deps) -> u64 {
-    executor_prefixes(deps.storage)
-        .range(None, None, Order::Ascending)
-        .count() as u64
+pub fn query_executor_bond(deps: Deps, executor: String) -> StdResult<ExecutorBondResponse> {
+    let bond = read_executor_bond(deps.storage, &deps.api.addr_canonicalize(&executor)?);
+    Ok(ExecutorBondResponse {
+        executor,
+        denom: bond.denom,
+        bonded: bond.amount,
+        unbonding: bond.unbonding,
+    })
+}
+
+pub fn query_executor_rewards(deps: Deps, executor: String) -> StdResult<ExecutorRewardsResponse> {
+    let rewards = read_executor_rewards(deps.storage, &deps.api.addr_canonicalize(&executor)?);
+    Ok(ExecutorRewardsResponse { executor, rewards })
 }
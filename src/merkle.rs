@@ -0,0 +1,465 @@
+//! Merkle trees as registered and verified by the contract. Executors build their trees with
+//! this module so that roots and proofs match `QueryMsg::VerifyData`.
+//!
+//! Pairs are hashed sorted, so proofs are plain lists of sibling hashes. A node left without
+//! sibling at the end of a layer moves up unchanged.
+//!
+//! ```
+//! use aioracle::merkle::{verify_proof, MerkleTree};
+//! use aioracle::state::{HashAlgorithm, TreeFormat};
+//!
+//! let leaves = [b"a", b"b", b"c"];
+//! let tree = MerkleTree::new(HashAlgorithm::Sha256, TreeFormat::Legacy, &leaves);
+//! assert_eq!(
+//!     tree.hex_root().unwrap(),
+//!     "aea2dd4249dcecf97ca6a1556db7f21ebd6a40bbec0243ca61b717146a08c347"
+//! );
+//! assert_eq!(
+//!     tree.hex_proof(0).unwrap(),
+//!     [
+//!         "3e23e8160039594a33894f6564e1b1348bbd7a0088d42c4acb73eeaed59c009d",
+//!         "2e7d2c03a9507ae265ecf5b5356885a53393a2029d241394997265a1a25aefc6",
+//!     ]
+//! );
+//! let root = tree.root().unwrap();
+//! for (index, leaf) in leaves.iter().enumerate() {
+//!     let proof = tree.proof(index).unwrap();
+//!     assert!(verify_proof(HashAlgorithm::Sha256, TreeFormat::Legacy, &root, *leaf, &proof));
+//! }
+//!
+//! let tree = MerkleTree::new(HashAlgorithm::Sha256, TreeFormat::V1, &leaves);
+//! assert_eq!(
+//!     tree.hex_root().unwrap(),
+//!     "97f341ec03c4edd084475dc4a2be23eec98d3ed72dd33fccdafaff0260b4b9f4"
+//! );
+//! ```
+
+use cosmwasm_std::{StdError, StdResult};
+use sha2::Digest;
+
+use crate::state::{HashAlgorithm, TreeFormat};
+
+pub type Hash = [u8; 32];
+
+// domain separation of TreeFormat::V1 hashes
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+pub struct MerkleTree {
+    algorithm: HashAlgorithm,
+    format: TreeFormat,
+    /// Hashed leaves first, the root alone in the last layer.
+    layers: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    pub fn new<T: AsRef<[u8]>>(algorithm: HashAlgorithm, format: TreeFormat, leaves: &[T]) -> Self {
+        let mut layers = vec![leaves
+            .iter()
+            .map(|leaf| hash_leaf(algorithm, format, leaf.as_ref()))
+            .collect::<Vec<Hash>>()];
+        while let Some(layer) = layers.last().filter(|layer| layer.len() > 1) {
+            let next = layer
+                .chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => hash_pair(algorithm, format, a, b),
+                    [a] => *a,
+                    _ => unreachable!(),
+                })
+                .collect();
+            layers.push(next);
+        }
+        MerkleTree {
+            algorithm,
+            format,
+            layers,
+        }
+    }
+
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    pub fn format(&self) -> TreeFormat {
+        self.format
+    }
+
+    /// None for a tree without leaves.
+    pub fn root(&self) -> Option<Hash> {
+        self.layers.last().and_then(|layer| layer.first()).copied()
+    }
+
+    /// The root as given to `ExecuteMsg::RegisterMerkleRoot`.
+    pub fn hex_root(&self) -> Option<String> {
+        self.root().map(hex::encode)
+    }
+
+    /// Sibling hashes from the leaf up to the root, None if the index is out of range.
+    pub fn proof(&self, index: usize) -> Option<Vec<Hash>> {
+        if index >= self.layers[0].len() {
+            return None;
+        }
+        let mut index = index;
+        let mut proof = vec![];
+        for layer in &self.layers[..self.layers.len() - 1] {
+            if let Some(sibling) = layer.get(index ^ 1) {
+                proof.push(*sibling);
+            }
+            index /= 2;
+        }
+        Some(proof)
+    }
+
+    /// The proof as given to `QueryMsg::VerifyData`.
+    pub fn hex_proof(&self, index: usize) -> Option<Vec<String>> {
+        self.proof(index)
+            .map(|proof| proof.into_iter().map(hex::encode).collect())
+    }
+
+    /// Proof and flags proving the leaves at the given indices at once, see `verify_multiproof`.
+    /// The indices must be strictly increasing, the leaves are then verified in that order.
+    /// None if there are no indices or they are unordered or out of range.
+    pub fn multiproof(&self, indices: &[usize]) -> Option<(Vec<Hash>, Vec<Option<bool>>)> {
+        if indices.is_empty()
+            || indices.windows(2).any(|pair| pair[0] >= pair[1])
+            || indices.iter().any(|&index| index >= self.layers[0].len())
+        {
+            return None;
+        }
+        let mut indices = indices.to_vec();
+        let mut proof = vec![];
+        let mut proof_flags = vec![];
+        for layer in &self.layers[..self.layers.len() - 1] {
+            let mut parents = vec![];
+            let mut known = indices.iter().peekable();
+            while let Some(&index) = known.next() {
+                let sibling = index ^ 1;
+                if sibling >= layer.len() {
+                    proof_flags.push(None);
+                } else if known.next_if(|&&next| next == sibling).is_some() {
+                    proof_flags.push(Some(true));
+                } else {
+                    proof.push(layer[sibling]);
+                    proof_flags.push(Some(false));
+                }
+                parents.push(index / 2);
+            }
+            indices = parents;
+        }
+        Some((proof, proof_flags))
+    }
+}
+
+/// Whether the data is a leaf of the root given its proof.
+pub fn verify_proof(
+    algorithm: HashAlgorithm,
+    format: TreeFormat,
+    root: &Hash,
+    data: &[u8],
+    proof: &[Hash],
+) -> bool {
+    let hash = proof
+        .iter()
+        .fold(hash_leaf(algorithm, format, data), |hash, sibling| {
+            hash_pair(algorithm, format, &hash, sibling)
+        });
+    &hash == root
+}
+
+/// Whether all leaves belong to the root given a multiproof. The tree is rebuilt bottom up
+/// consuming the leaves in order, then the computed hashes. Each flag tells whether the sibling
+/// of the next node is a leaf or computed hash (true), comes from the proof (false) or whether
+/// the node has no sibling and moves up unchanged (none).
+///
+/// This is not the OpenZeppelin `MerkleProof.multiProofVerify` format: the flags have a third
+/// state for nodes without sibling and the leaves come in increasing tree order, so proofs
+/// must be built with `MerkleTree::multiproof` rather than with OpenZeppelin tooling.
+pub fn verify_multiproof<T: AsRef<[u8]>>(
+    algorithm: HashAlgorithm,
+    format: TreeFormat,
+    root: &Hash,
+    leaves: &[T],
+    proof: &[Hash],
+    proof_flags: &[Option<bool>],
+) -> StdResult<bool> {
+    let pairs = proof_flags.iter().filter(|flag| flag.is_some()).count();
+    if leaves.is_empty() || leaves.len() + proof.len() != pairs + 1 {
+        return Err(StdError::generic_err("invalid multiproof"));
+    }
+    let mut leaves = leaves
+        .iter()
+        .map(|leaf| hash_leaf(algorithm, format, leaf.as_ref()));
+    let mut proof = proof.iter().copied();
+    let mut hashes: Vec<Hash> = Vec::with_capacity(proof_flags.len());
+    let mut hash_pos = 0;
+    let mut next_node = |hashes: &Vec<Hash>| {
+        leaves.next().or_else(|| {
+            hash_pos += 1;
+            hashes.get(hash_pos - 1).copied()
+        })
+    };
+    for &flag in proof_flags {
+        let Some(a) = next_node(&hashes) else {
+            return Err(StdError::generic_err("invalid multiproof"));
+        };
+        let b = match flag {
+            Some(true) => next_node(&hashes),
+            Some(false) => proof.next(),
+            None => {
+                hashes.push(a);
+                continue;
+            }
+        };
+        let Some(b) = b else {
+            return Err(StdError::generic_err("invalid multiproof"));
+        };
+        hashes.push(hash_pair(algorithm, format, &a, &b));
+    }
+    // the root is the only node left
+    let computed_root = next_node(&hashes);
+    if next_node(&hashes).is_some() || proof.next().is_some() {
+        return Err(StdError::generic_err("invalid multiproof"));
+    }
+    Ok(computed_root.as_ref() == Some(root))
+}
+
+pub fn hash(algorithm: HashAlgorithm, data: &[u8]) -> Hash {
+    match algorithm {
+        HashAlgorithm::Sha256 => sha2::Sha256::digest(data).into(),
+        HashAlgorithm::Keccak256 => <sha3::Keccak256 as sha3::Digest>::digest(data).into(),
+    }
+}
+
+pub fn hash_leaf(algorithm: HashAlgorithm, format: TreeFormat, data: &[u8]) -> Hash {
+    match format {
+        TreeFormat::Legacy => hash(algorithm, data),
+        TreeFormat::V1 => hash(algorithm, &[&[LEAF_PREFIX], data].concat()),
+    }
+}
+
+/// Sorted pair hashing, the order of siblings does not matter.
+pub fn hash_pair(algorithm: HashAlgorithm, format: TreeFormat, a: &Hash, b: &Hash) -> Hash {
+    let mut hashes = [*a, *b];
+    hashes.sort_unstable();
+    match format {
+        TreeFormat::Legacy => hash(algorithm, &hashes.concat()),
+        TreeFormat::V1 => hash(
+            algorithm,
+            &[&[NODE_PREFIX][..], &hashes[0], &hashes[1]].concat(),
+        ),
+    }
+}
+
+pub fn decode_hash(hash: &str) -> StdResult<Hash> {
+    let mut hash_buf: Hash = [0; 32];
+    hex::decode_to_slice(hash, &mut hash_buf)
+        .map_err(|_| StdError::generic_err("error decoding"))?;
+    Ok(hash_buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(count: usize) -> Vec<Vec<u8>> {
+        (0..count).map(|i| vec![b'a' + i as u8]).collect()
+    }
+
+    // (algorithm, format, root of a..c, root of a..e, proof of c in a..e), computed apart
+    // from this module with Python's hashlib and OpenSSL's keccak-256
+    const VECTORS: [(HashAlgorithm, TreeFormat, &str, &str, [&str; 3]); 4] = [
+        (
+            HashAlgorithm::Sha256,
+            TreeFormat::Legacy,
+            "aea2dd4249dcecf97ca6a1556db7f21ebd6a40bbec0243ca61b717146a08c347",
+            "930747c3ad2cac9fdc0cc025207d282e4f5f055169d11aaa320ddb0d133e2ef8",
+            [
+                "18ac3e7343f016890c510e93f935261169d9e3f565436429830faf0934f4f8e4",
+                "18d79cb747ea174c59f3a3b41768672526d56fecc58360a99d283d0f9b0a3cc0",
+                "3f79bb7b435b05321651daefd374cdc681dc06faa65e374e38337b88ca046dea",
+            ],
+        ),
+        (
+            HashAlgorithm::Sha256,
+            TreeFormat::V1,
+            "97f341ec03c4edd084475dc4a2be23eec98d3ed72dd33fccdafaff0260b4b9f4",
+            "9df74ee350081969147e2273cbfab65f01d280d399333cc125ba0a1192ca58d4",
+            [
+                "d070dc5b8da9aea7dc0f5ad4c29d89965200059c9a0ceca3abd5da2492dcb71d",
+                "b137985ff484fb600db93107c77b0365c80d78f5b429ded0fd97361d077999eb",
+                "2824a7ccda2caa720c85c9fba1e8b5b735eecfdb03878e4f8dfe6c3625030bc4",
+            ],
+        ),
+        (
+            HashAlgorithm::Keccak256,
+            TreeFormat::Legacy,
+            "5842148bc6ebeb52af882a317c765fccd3ae80589b21a9b8cbf21abb630e46a7",
+            "1dd0d2a6ae466d665cb26e1a31f07c57ae5df7d2bc559cd5826d417be9141a5d",
+            [
+                "f1918e8562236eb17adc8502332f4c9c82bc14e19bfc0aa10ab674ff75b3d2f3",
+                "805b21d846b189efaeb0377d6bb0d201b3872a363e607c25088f025b0c6ae1f8",
+                "a8982c89d80987fb9a510e25981ee9170206be21af3c8e0eb312ef1d3382e761",
+            ],
+        ),
+        (
+            HashAlgorithm::Keccak256,
+            TreeFormat::V1,
+            "3f6c2d6d0c2fcd67795ea50af0dc85c8e2df8832efe3c49e36d8fe2e71bcc07b",
+            "10f22010c13ba1f348d42d9b46b0cd525d1e4f32c9e2a97b57ebb6ae67629d1d",
+            [
+                "2ce80d2bc0bfe54c2499d066ac958c02304ce64ca318ae19a4636c32d583429c",
+                "00d25e3ecfd5a8430c58b5562d4a00f53ce3e76001e3683df8496c541fecb9da",
+                "ad874791597853f0fd83d9000a33497788d377d86eccb6504942fb20072c0bf5",
+            ],
+        ),
+    ];
+
+    #[test]
+    fn tree_vectors() {
+        for (algorithm, format, root_3, root_5, proof_5) in VECTORS {
+            let tree = MerkleTree::new(algorithm, format, &leaves(3));
+            assert_eq!(tree.hex_root().unwrap(), root_3);
+
+            let tree = MerkleTree::new(algorithm, format, &leaves(5));
+            assert_eq!(tree.hex_root().unwrap(), root_5);
+            assert_eq!(tree.hex_proof(2).unwrap(), proof_5);
+            let proof: Vec<Hash> = proof_5.iter().map(|p| decode_hash(p).unwrap()).collect();
+            let root = decode_hash(root_5).unwrap();
+            assert!(verify_proof(algorithm, format, &root, b"c", &proof));
+            assert!(!verify_proof(algorithm, format, &root, b"d", &proof));
+        }
+    }
+
+    #[test]
+    fn multiproof_every_subset() {
+        for format in [TreeFormat::Legacy, TreeFormat::V1] {
+            for count in 1..=9 {
+                let leaves = leaves(count);
+                let tree = MerkleTree::new(HashAlgorithm::Sha256, format, &leaves);
+                let root = tree.root().unwrap();
+                for subset in 1..1usize << count {
+                    let indices: Vec<usize> =
+                        (0..count).filter(|i| subset & (1 << i) != 0).collect();
+                    let subset_leaves: Vec<&Vec<u8>> =
+                        indices.iter().map(|&i| &leaves[i]).collect();
+                    let (proof, proof_flags) = tree.multiproof(&indices).unwrap();
+                    assert!(verify_multiproof(
+                        HashAlgorithm::Sha256,
+                        format,
+                        &root,
+                        &subset_leaves,
+                        &proof,
+                        &proof_flags
+                    )
+                    .unwrap());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn multiproof_promoted_node() {
+        // e has no sibling on the first two layers: root = H(H(H(a, b), H(c, d)), e)
+        let leaves = leaves(5);
+        let tree = MerkleTree::new(HashAlgorithm::Sha256, TreeFormat::V1, &leaves);
+        let root = tree.root().unwrap();
+
+        let (proof, proof_flags) = tree.multiproof(&[0, 1, 2, 3, 4]).unwrap();
+        assert!(proof.is_empty());
+        assert_eq!(
+            proof_flags,
+            [Some(true), Some(true), None, Some(true), None, Some(true)]
+        );
+        assert!(verify_multiproof(
+            HashAlgorithm::Sha256,
+            TreeFormat::V1,
+            &root,
+            &leaves,
+            &[],
+            &proof_flags
+        )
+        .unwrap());
+
+        let (proof, proof_flags) = tree.multiproof(&[3, 4]).unwrap();
+        assert_eq!(proof, [tree.layers[0][2], tree.layers[1][0]]);
+        assert_eq!(
+            proof_flags,
+            [Some(false), None, Some(false), None, Some(true)]
+        );
+        assert!(verify_multiproof(
+            HashAlgorithm::Sha256,
+            TreeFormat::V1,
+            &root,
+            &leaves[3..],
+            &proof,
+            &proof_flags
+        )
+        .unwrap());
+        // a promoted leaf cannot stand in for another one
+        assert!(!verify_multiproof(
+            HashAlgorithm::Sha256,
+            TreeFormat::V1,
+            &root,
+            &[&leaves[3], &leaves[0]],
+            &proof,
+            &proof_flags
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn multiproof_without_promotion_flags() {
+        let leaves = leaves(4);
+        let tree = MerkleTree::new(HashAlgorithm::Keccak256, TreeFormat::Legacy, &leaves);
+        let proof = [tree.layers[0][1], tree.layers[1][1]];
+        let proof_flags = [Some(false), Some(false)];
+        assert!(verify_multiproof(
+            HashAlgorithm::Keccak256,
+            TreeFormat::Legacy,
+            &tree.root().unwrap(),
+            &leaves[..1],
+            &proof,
+            &proof_flags
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn multiproof_rejects_empty_leaves() {
+        let leaves = leaves(2);
+        let tree = MerkleTree::new(HashAlgorithm::Sha256, TreeFormat::V1, &leaves);
+        let root = tree.root().unwrap();
+        // the root given as proof would otherwise verify without any leaf
+        assert!(verify_multiproof::<&[u8]>(
+            HashAlgorithm::Sha256,
+            TreeFormat::V1,
+            &root,
+            &[],
+            &[root],
+            &[]
+        )
+        .is_err());
+        assert!(tree.multiproof(&[]).is_none());
+    }
+
+    #[test]
+    fn multiproof_rejects_unused_nodes() {
+        let leaves = leaves(3);
+        let tree = MerkleTree::new(HashAlgorithm::Sha256, TreeFormat::V1, &leaves);
+        let root = tree.root().unwrap();
+        let (proof, proof_flags) = tree.multiproof(&[2]).unwrap();
+        assert_eq!(proof_flags, [None, Some(false)]);
+        // one more leaf and a flag hashing nothing but the leaf itself
+        assert!(verify_multiproof(
+            HashAlgorithm::Sha256,
+            TreeFormat::V1,
+            &root,
+            &[&leaves[2], &leaves[0]],
+            &proof,
+            &[None, None, Some(false)]
+        )
+        .is_err());
+        assert!(tree.multiproof(&[1, 0]).is_none());
+        assert!(tree.multiproof(&[3]).is_none());
+    }
+}
@@ -1,7 +1,10 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Addr, Binary};
+use cosmwasm_std::{Addr, Binary, Coin, Decimal, Uint128};
 
-use crate::state::{DataSourceState, Service, ServiceInfo, TestCaseState};
+use crate::state::{
+    Callback, Challenge, DataSourceState, HashAlgorithm, RequestStatus, Service, ServiceInfo,
+    TestCaseState, TreeFormat, Unbonding,
+};
 
 #[cw_serde]
 pub struct ServiceMsg {
@@ -9,14 +12,24 @@ pub struct ServiceMsg {
 }
 
 #[cw_serde]
-pub struct ServiceFeesMsg {
-    pub addr: Addr,
+pub struct ServiceInfoResponse {
+    pub service_name: String,
+    pub service_info: ServiceInfo,
 }
 
+/// A page of services, `next_start_after` is set when more may follow.
 #[cw_serde]
-pub struct ServiceInfoResponse {
+pub struct ServiceListResponse {
+    pub items: Vec<ServiceInfoResponse>,
+    pub next_start_after: Option<String>,
+    pub total: Option<u64>,
+}
+
+#[cw_serde]
+pub struct ServiceFeesResponse {
     pub service_name: String,
-    pub service_info: ServiceInfo,
+    /// Total amount a requester must attach to `ExecuteMsg::Request`, grouped by denom.
+    pub fees: Vec<Coin>,
 }
 
 #[cw_serde]
@@ -24,6 +37,23 @@ pub struct InstantiateMsg {
     /// Owner if none set to info.sender.
     pub owner: Option<Addr>,
     pub executors: Option<Vec<String>>,
+    /// Blocks after which an unfinished request expires, never if not set.
+    pub request_timeout: Option<u64>,
+    /// Blocks during which a registered merkle root can be challenged, 0 if not set. Merkle
+    /// roots keep the period they were registered with when it changes.
+    pub challenge_period: Option<u64>,
+    /// Deposit a challenge must come with, none required if not set.
+    pub challenge_deposit: Option<Coin>,
+    /// Bond executors need to take part in requests, no bond required if not set.
+    pub min_executor_bond: Option<Coin>,
+    pub unbonding_period: Option<u64>,
+    pub slash_fraction: Option<Decimal>,
+    pub jail_period: Option<u64>,
+    pub burn_slashed: Option<bool>,
+    /// Lets the owner register merkle roots without executor signatures, off if not set.
+    pub owner_registration: Option<bool>,
+    /// Blocks per executor epoch, executor changes apply immediately if not set.
+    pub epoch_length: Option<u64>,
 }
 
 #[cw_serde]
@@ -31,22 +61,139 @@ pub enum ExecuteMsg {
     UpdateConfig {
         update_config_msg: UpdateConfigMsg,
     },
+    /// Registers a merkle root on behalf of the executors. Owner only, when
+    /// `Config::owner_registration` is enabled.
     RegisterMerkleRoot {
         /// MerkleRoot is hex-encoded merkle root.
         stage: u64,
         merkle_root: String,
         executors: Vec<String>,
     },
+    /// Submits executor signatures over a merkle root, anyone can relay them.
+    /// Signatures are accumulated across calls and the root is registered once
+    /// `threshold` distinct executors signed it.
+    SubmitMerkleRoot {
+        stage: u64,
+        /// MerkleRoot is hex-encoded merkle root.
+        merkle_root: String,
+        signatures: Vec<ExecutorSignature>,
+    },
     Request {
         service: String,
         input: Option<String>,
         threshold: u64,
+        /// Contract to notify with `CallbackMsg` once the merkle root is final.
+        callback: Option<RequestCallback>,
     },
     AddService(AddServiceMsg),
     UpdateService(UpdateServiceMsg),
     DeleteService {
         service_name: String,
     },
+    /// Withdraws all rewards the sender earned as an executor.
+    ClaimReward {},
+    /// Sets the key the sender signs merkle roots with as an executor.
+    RotateExecutorKey {
+        /// Compressed secp256k1 public key.
+        pubkey: Binary,
+        /// Signature with the new key over sha256(contract address | executor address | pubkey).
+        signature: Binary,
+    },
+    UpdateExecutorMetadata {
+        metadata: Option<String>,
+    },
+    /// Adds the attached bond denom funds to the sender's executor bond.
+    BondExecutor {},
+    /// Starts unbonding part of the sender's executor bond, not while a challenge is open
+    /// against a merkle root the sender registered.
+    UnbondExecutor {
+        amount: Uint128,
+    },
+    /// Withdraws unbonded funds whose unbonding period has passed, once no challenge is open
+    /// against a merkle root the sender registered.
+    WithdrawUnbonded {},
+    /// Brings the sender back in the executor set once its jail period is over.
+    UnjailExecutor {},
+    /// Returns the fees of an expired request to its requester.
+    ReclaimRequest {
+        stage: u64,
+    },
+    /// Cancels a pending request and refunds its fees. Owner only.
+    CancelRequest {
+        stage: u64,
+    },
+    /// Finalizes a merkle root whose challenge period has passed.
+    FinalizeMerkleRoot {
+        stage: u64,
+    },
+    /// Disputes a merkle root during its challenge period. Executors only, with the challenge
+    /// deposit attached.
+    ChallengeMerkleRoot {
+        stage: u64,
+        evidence: String,
+    },
+    /// Stores a result on-chain once proven against the merkle root of the stage. Only the
+    /// requester or the executors who served the request may submit.
+    SubmitResult {
+        stage: u64,
+        data: Binary,
+        proof: Option<Vec<String>>,
+    },
+    /// Settles a challenge. Owner only. An upheld challenge discards the merkle root and
+    /// slashes the executors who registered it, otherwise the root is finalized.
+    ResolveChallenge {
+        stage: u64,
+        upheld: bool,
+    },
+    /// Asks to become an executor, the sender waits in the application queue until the owner
+    /// approves or rejects it. Applying again replaces the pending application.
+    ApplyAsExecutor {
+        /// Compressed secp256k1 public key.
+        pubkey: Binary,
+        /// Signature with the key over sha256(contract address | applicant address | pubkey).
+        signature: Binary,
+        metadata: Option<String>,
+    },
+    /// Adds the applicant to the executors with the next executor changes. Owner only.
+    ApproveExecutor {
+        applicant: String,
+    },
+    /// Drops a pending application. Owner only.
+    RejectExecutor {
+        applicant: String,
+    },
+    /// Backfills the status, service and requester counts of the next requests stored before the last
+    /// migration. Owner only.
+    MigrateRequests {
+        limit: Option<u64>,
+    },
+}
+
+#[cw_serde]
+pub struct RequestCallback {
+    pub contract: String,
+    pub msg: Option<Binary>,
+}
+
+/// Message executed on the callback contract of a request once its merkle root is final.
+#[cw_serde]
+pub enum CallbackMsg {
+    AioracleCallback {
+        stage: u64,
+        merkle_root: String,
+        /// The `msg` given with the request callback.
+        msg: Option<Binary>,
+    },
+}
+
+#[cw_serde]
+pub struct ExecutorSignature {
+    pub executor: String,
+    /// Compressed secp256k1 public key of the executor account. Only needed when the executor
+    /// has not registered a key, otherwise it must match the registered key.
+    pub pubkey: Option<Binary>,
+    /// Signature over sha256(contract address | stage as big endian u64 | merkle root bytes).
+    pub signature: Binary,
 }
 
 #[cw_serde]
@@ -56,6 +203,12 @@ pub struct UpdateServiceMsg {
     pub dsources: Option<Vec<DataSourceState>>,
     pub tcases: Option<Vec<TestCaseState>>,
     pub oscript_url: Option<String>,
+    pub fee: Option<Coin>,
+    pub request_timeout: Option<u64>,
+    pub hash_algorithm: Option<HashAlgorithm>,
+    pub tree_format: Option<TreeFormat>,
+    /// Restricts the service to these executors, an empty list opens it to all executors again.
+    pub allowed_executors: Option<Vec<String>>,
 }
 
 #[cw_serde]
@@ -69,53 +222,141 @@ pub struct AddServiceMsg {
 pub enum QueryMsg {
     #[returns(crate::state::Config)]
     Config {},
-    #[returns(Vec<String>)]
+    #[returns(ExecutorListResponse)]
     GetExecutors {
         start: Option<String>, // ordered by keys, so we pass in executor addresses
         end: Option<String>,
+        /// `next_start_after` of the previous page.
+        start_after: Option<String>,
         order: Option<u8>,
         limit: Option<u8>,
     },
+    #[returns(ExecutorsResponse)]
+    GetExecutor { address: String },
     #[returns(bool)]
     CheckExecutorInList { address: String },
+    /// Whether the executor was in the active set at the given version.
+    #[returns(bool)]
+    CheckExecutorInSet { address: String, version: u64 },
+    #[returns(u64)]
+    GetExecutorSetVersion {},
     #[returns(u64)]
     GetExecutorSize {},
+    /// Executors of the current epoch, including queued changes already due.
+    #[returns(EpochExecutorsResponse)]
+    GetCurrentEpochExecutors {
+        start_after: Option<String>,
+        limit: Option<u8>,
+    },
+    /// Executors of the next epoch, once the queued changes apply.
+    #[returns(EpochExecutorsResponse)]
+    GetNextEpochExecutors {
+        start_after: Option<String>,
+        limit: Option<u8>,
+    },
+    #[returns(ExecutorRewardsResponse)]
+    GetExecutorRewards { executor: String },
+    #[returns(ExecutorBondResponse)]
+    GetExecutorBond { executor: String },
+    #[returns(ExecutorApplicationResponse)]
+    GetExecutorApplication { applicant: String },
+    /// Applications ordered by canonical address.
+    #[returns(ExecutorApplicationListResponse)]
+    GetExecutorApplications {
+        start_after: Option<String>,
+        limit: Option<u8>,
+    },
     #[returns(RequestResponse)]
     GetRequest { stage: u64 },
-    #[returns(Vec<RequestResponse>)]
+    #[returns(RequestListResponse)]
     GetRequests {
         offset: Option<u64>,
         limit: Option<u8>,
         order: Option<u8>,
     },
-    #[returns(Vec<RequestResponse>)]
+    #[returns(RequestListResponse)]
     GetRequestsByService {
         service: String,
         offset: Option<u64>,
         limit: Option<u8>,
         order: Option<u8>,
     },
-    #[returns(Vec<RequestResponse>)]
+    /// Requests by status as reported by `GetRequest`, timed out requests are listed as
+    /// expired. A page may hold less than `limit` requests while `next_start_after` is set.
+    /// Until `MigrateRequests` went through the requests stored before statuses, every stage
+    /// is searched instead of the status index.
+    #[returns(RequestListResponse)]
+    GetRequestsByStatus {
+        status: RequestStatus,
+        offset: Option<u64>,
+        limit: Option<u8>,
+        order: Option<u8>,
+    },
+    #[returns(RequestListResponse)]
     GetRequestsByMerkleRoot {
         merkle_root: String,
         offset: Option<u64>,
         limit: Option<u8>,
         order: Option<u8>,
     },
+    #[returns(RequestListResponse)]
+    GetRequestsByRequester {
+        requester: String,
+        offset: Option<u64>,
+        limit: Option<u8>,
+        order: Option<u8>,
+    },
+    #[returns(u64)]
+    GetRequestsCountByRequester { requester: String },
+    /// Requests matching all the given filters, a page may hold less than `limit` requests
+    /// while `next_offset` is set.
+    #[returns(SearchRequestsResponse)]
+    SearchRequests {
+        filter: RequestFilter,
+        offset: Option<u64>,
+        limit: Option<u8>,
+        order: Option<u8>,
+    },
     #[returns(LatestStageResponse)]
     LatestStage {},
-    #[returns(bool)]
+    #[returns(Vec<Addr>)]
+    GetMerkleRootSigners { stage: u64, merkle_root: String },
+    #[returns(Challenge)]
+    GetChallenge { stage: u64 },
+    #[returns(VerifyDataResponse)]
     VerifyData {
         stage: u64,
         data: Binary,
         proof: Option<Vec<String>>,
     },
+    /// Verifies several leaves of a stage with one multiproof, see
+    /// `merkle::verify_multiproof`. The format differs from OpenZeppelin multiproofs, which
+    /// can not be verified here.
+    #[returns(VerifyDataResponse)]
+    VerifyDataMulti {
+        stage: u64,
+        /// Leaves in the order expected by the multiproof.
+        leaves: Vec<Binary>,
+        proof: Vec<String>,
+        /// Null for a node without sibling, which moves up unchanged.
+        proof_flags: Vec<Option<bool>>,
+    },
+    /// Verifies many leaves at once, a failing item does not fail the query. Up to
+    /// `MAX_VERIFY_BATCH` items.
+    #[returns(Vec<VerifyDataBatchResponse>)]
+    VerifyDataBatch { items: Vec<VerifyDataItem> },
+    #[returns(RequestResultResponse)]
+    GetRequestResult { stage: u64 },
     #[returns(ServiceInfo)]
     GetService { service_name: String },
-    #[returns(Vec<ServiceInfoResponse>)]
+    #[returns(ServiceFeesResponse)]
+    GetServiceFees { service_name: String },
+    #[returns(ServiceListResponse)]
     GetServices {
         start: Option<String>, // ordered by keys, so we pass in executor addresses
         end: Option<String>,
+        /// `next_start_after` of the previous page.
+        start_after: Option<String>,
         order: Option<u8>,
         limit: Option<u8>,
     },
@@ -128,8 +369,59 @@ pub struct ConfigResponse {
 
 #[cw_serde]
 pub struct ExecutorsResponse {
+    pub executor: String,
+    pub pubkey: Option<Binary>,
+    pub is_active: bool,
+    pub joined_height: u64,
+    pub metadata: Option<String>,
+    pub jailed_until: Option<u64>,
+}
+
+/// A page of executors, `next_start_after` is set when more may follow.
+#[cw_serde]
+pub struct ExecutorListResponse {
+    pub items: Vec<ExecutorsResponse>,
+    pub next_start_after: Option<String>,
+    pub total: Option<u64>,
+}
+
+/// A page of the executors of an epoch, ordered by canonical address.
+#[cw_serde]
+pub struct EpochExecutorsResponse {
+    pub epoch: u64,
+    pub start_height: u64,
+    pub executors: Vec<String>,
+    pub next_start_after: Option<String>,
+}
+
+#[cw_serde]
+pub struct ExecutorApplicationResponse {
+    pub applicant: String,
     pub pubkey: Binary,
-    pub is_acitve: bool,
+    pub metadata: Option<String>,
+    pub height: u64,
+    pub approved: bool,
+}
+
+/// A page of executor applications, `next_start_after` is set when more may follow.
+#[cw_serde]
+pub struct ExecutorApplicationListResponse {
+    pub items: Vec<ExecutorApplicationResponse>,
+    pub next_start_after: Option<String>,
+}
+
+#[cw_serde]
+pub struct ExecutorRewardsResponse {
+    pub executor: String,
+    pub rewards: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct ExecutorBondResponse {
+    pub executor: String,
+    pub denom: String,
+    pub bonded: Uint128,
+    pub unbonding: Vec<Unbonding>,
 }
 
 #[cw_serde]
@@ -144,6 +436,75 @@ pub struct RequestResponse {
     pub threshold: u64,
     pub service: String,
     pub input: Option<String>,
+    pub fees: Vec<Coin>,
+    pub timeout_height: Option<u64>,
+    pub status: RequestStatus,
+    pub executors: Vec<Addr>,
+    pub callback: Option<Callback>,
+    pub hash_algorithm: HashAlgorithm,
+    pub tree_format: TreeFormat,
+    pub executor_set_version: Option<u64>,
+    pub allowed_executors: Option<Vec<Addr>>,
+    pub challenge_end_height: Option<u64>,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct RequestFilter {
+    pub service: Option<String>,
+    pub requester: Option<String>,
+    pub status: Option<RequestStatus>,
+    /// Inclusive bounds on the request height.
+    pub min_height: Option<u64>,
+    pub max_height: Option<u64>,
+    /// Whether the merkle root is final, finalized or past its challenge period.
+    pub finalized: Option<bool>,
+}
+
+#[cw_serde]
+pub struct SearchRequestsResponse {
+    pub requests: Vec<RequestResponse>,
+    /// Offset of the next page, none once all requests were searched.
+    pub next_offset: Option<u64>,
+}
+
+#[cw_serde]
+pub struct VerifyDataItem {
+    pub stage: u64,
+    pub data: Binary,
+    pub proof: Option<Vec<String>>,
+}
+
+#[cw_serde]
+pub struct VerifyDataBatchResponse {
+    pub stage: u64,
+    pub verified: bool,
+    /// Why the item could not be verified, e.g. no merkle root or a malformed proof.
+    pub error: Option<String>,
+}
+
+#[cw_serde]
+pub struct RequestResultResponse {
+    pub stage: u64,
+    pub merkle_root: String,
+    pub results: Vec<Binary>,
+}
+
+/// A page of requests, pass `next_start_after` as offset to get the next one.
+#[cw_serde]
+pub struct RequestListResponse {
+    pub items: Vec<RequestResponse>,
+    pub next_start_after: Option<u64>,
+    pub total: Option<u64>,
+}
+
+#[cw_serde]
+pub struct VerifyDataResponse {
+    pub verified: bool,
+    /// The merkle root can no longer be challenged.
+    pub finalized: bool,
+    /// The merkle root is still within its challenge period.
+    pub challengeable: bool,
 }
 
 #[cw_serde]
@@ -155,9 +516,20 @@ pub struct LatestStageResponse {
 pub struct MigrateMsg {}
 
 #[cw_serde]
+#[derive(Default)]
 pub struct UpdateConfigMsg {
     pub new_owner: Option<Addr>,
     pub new_executors: Option<Vec<String>>,
     pub old_executors: Option<Vec<String>>,
     pub new_max_req_threshold: Option<u64>,
+    pub new_request_timeout: Option<u64>,
+    pub new_challenge_period: Option<u64>,
+    pub new_challenge_deposit: Option<Coin>,
+    pub new_min_executor_bond: Option<Coin>,
+    pub new_unbonding_period: Option<u64>,
+    pub new_slash_fraction: Option<Decimal>,
+    pub new_jail_period: Option<u64>,
+    pub new_burn_slashed: Option<bool>,
+    pub new_owner_registration: Option<bool>,
+    pub new_epoch_length: Option<u64>,
 }
@@ -1,16 +1,50 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, CanonicalAddr, Order, StdResult, Storage};
-
-use cosmwasm_storage::{bucket, bucket_read, prefixed, prefixed_read, singleton, singleton_read};
+use cosmwasm_schema::serde::{Deserialize, Serialize};
+use cosmwasm_std::{
+    from_slice, Addr, Binary, CanonicalAddr, Coin, Decimal, Order, StdResult, Storage, Uint128,
+};
+
+use cosmwasm_storage::{
+    bucket, bucket_read, prefixed, prefixed_read, singleton, singleton_read, Bucket, ReadonlyBucket,
+};
 use cw_storage_plus::{Bound, Bounder, Index, IndexList, IndexedMap, KeyDeserialize, MultiIndex};
 
-use crate::msg::ServiceInfoResponse;
+use crate::msg::{ServiceInfoResponse, UpdateConfigMsg};
 
 #[cw_serde]
 pub struct Config {
     /// Owner If None set, contract is frozen.
     pub owner: Addr,
     pub max_req_threshold: u64,
+    /// Number of blocks after which a request without merkle root expires. None means never.
+    pub request_timeout: Option<u64>,
+    /// Number of blocks during which a registered merkle root can be challenged.
+    #[serde(default)]
+    pub challenge_period: u64,
+    /// Deposit put up with a challenge, lost to the executors of the root if it is rejected.
+    /// None means challenges need no deposit.
+    pub challenge_deposit: Option<Coin>,
+    /// Bond an executor needs to take part in requests. None means no bond is required.
+    pub min_executor_bond: Option<Coin>,
+    /// Number of blocks unbonded tokens stay locked before they can be withdrawn.
+    #[serde(default)]
+    pub unbonding_period: u64,
+    /// Share of the bond taken from executors who registered a merkle root shown to be wrong.
+    #[serde(default)]
+    pub slash_fraction: Decimal,
+    /// Number of blocks slashed executors are excluded from the executor set.
+    #[serde(default)]
+    pub jail_period: u64,
+    /// Burn slashed funds instead of rewarding them to the challenger.
+    #[serde(default)]
+    pub burn_slashed: bool,
+    /// Lets the owner register merkle roots without executor signatures.
+    #[serde(default)]
+    pub owner_registration: bool,
+    /// Number of blocks per executor epoch, executor changes wait for the next epoch to apply.
+    /// 0 applies them immediately.
+    #[serde(default)]
+    pub epoch_length: u64,
 }
 
 #[cw_serde]
@@ -23,6 +57,160 @@ pub struct Request {
     pub threshold: u64,
     pub service: String,
     pub input: Option<String>,
+    /// Fees paid by the requester, held by the contract until the request is processed.
+    #[serde(default)]
+    pub fees: Vec<Coin>,
+    /// Height from which the request is expired if no merkle root has been registered.
+    pub timeout_height: Option<u64>,
+    #[serde(default)]
+    pub status: RequestStatus,
+    /// Executors who served the request, rewarded once the merkle root is final.
+    #[serde(default)]
+    pub executors: Vec<Addr>,
+    /// Contract notified once the merkle root is final.
+    pub callback: Option<Callback>,
+    /// Taken from the service when the request is made.
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
+    /// Taken from the service when the request is made.
+    #[serde(default)]
+    pub tree_format: TreeFormat,
+    /// Version of the executor set eligible to serve the request, none for requests made
+    /// before executor sets were versioned.
+    pub executor_set_version: Option<u64>,
+    /// Taken from the service when the request is made, any executor may serve the request
+    /// when not set.
+    pub allowed_executors: Option<Vec<Addr>>,
+    /// Height from which the registered merkle root can no longer be challenged, set from the
+    /// challenge period when the root is registered.
+    #[serde(default)]
+    pub challenge_end_height: Option<u64>,
+}
+
+#[cw_serde]
+pub struct Callback {
+    pub contract: Addr,
+    /// Passed back untouched to the contract along with the stage and merkle root.
+    pub msg: Option<Binary>,
+}
+
+/// Hash function the merkle tree of a stage is built with.
+#[cw_serde]
+#[derive(Default, Copy)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    /// For trees that are also verified on EVM chains.
+    Keccak256,
+}
+
+/// How leaves and internal nodes of a merkle tree are hashed.
+#[cw_serde]
+#[derive(Default, Copy)]
+pub enum TreeFormat {
+    /// Leaves and nodes are hashed alike, kept for the stages built that way.
+    #[default]
+    Legacy,
+    /// Leaves are hashed with a 0x00 prefix and nodes with a 0x01 prefix as in RFC 6962,
+    /// so a node can not be passed off as a leaf.
+    V1,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub enum RequestStatus {
+    /// Waiting for a merkle root.
+    #[default]
+    Pending,
+    /// Merkle root registered, can still be challenged.
+    Submitted,
+    Finalized,
+    Challenged,
+    /// Timed out without merkle root. Queries report it once the timeout passes, it is stored
+    /// when the requester reclaims the fees.
+    Expired,
+    /// Cancelled by the owner, the fees went back to the requester.
+    Cancelled,
+}
+
+impl RequestStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            RequestStatus::Pending => "pending",
+            RequestStatus::Submitted => "submitted",
+            RequestStatus::Finalized => "finalized",
+            RequestStatus::Challenged => "challenged",
+            RequestStatus::Expired => "expired",
+            RequestStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+#[cw_serde]
+pub struct Executor {
+    /// Key the executor signs merkle roots with, the account key is expected when not set.
+    pub pubkey: Option<Binary>,
+    pub is_active: bool,
+    pub joined_height: u64,
+    pub metadata: Option<String>,
+    /// Height until which the executor is jailed after being slashed.
+    pub jailed_until: Option<u64>,
+}
+
+/// Request of an account to become an executor, kept until the owner decides on it.
+#[cw_serde]
+pub struct ExecutorApplication {
+    pub pubkey: Binary,
+    pub metadata: Option<String>,
+    pub height: u64,
+    /// Approved applications wait for the executor changes of the next epoch to apply.
+    pub approved: bool,
+}
+
+/// Executor changes queued until the start of the next epoch.
+#[cw_serde]
+pub struct ExecutorChanges {
+    pub new_executors: Vec<CanonicalAddr>,
+    pub old_executors: Vec<CanonicalAddr>,
+    pub apply_height: u64,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct ExecutorBond {
+    /// Denom the bond was deposited in.
+    #[serde(default)]
+    pub denom: String,
+    pub amount: Uint128,
+    pub unbonding: Vec<Unbonding>,
+}
+
+impl ExecutorBond {
+    pub fn is_empty(&self) -> bool {
+        self.amount.is_zero() && self.unbonding.is_empty()
+    }
+}
+
+#[cw_serde]
+pub struct Unbonding {
+    #[serde(default)]
+    pub denom: String,
+    pub amount: Uint128,
+    pub release_height: u64,
+}
+
+#[cw_serde]
+pub struct Challenge {
+    pub challenger: Addr,
+    /// The challenged merkle root, kept since an upheld challenge removes it from the request.
+    pub merkle_root: String,
+    pub evidence: String,
+    pub height: u64,
+    /// Returned to the challenger if the challenge is upheld.
+    #[serde(default)]
+    pub deposit: Vec<Coin>,
+    /// Outcome of the challenge, None until the owner resolves it.
+    pub upheld: Option<bool>,
 }
 
 #[cw_serde]
@@ -30,6 +218,8 @@ pub struct DataSourceState {
     pub language: String,
     pub script_url: String,
     pub parameters: Vec<String>,
+    /// Extra fee charged per request for running this data source.
+    pub fee: Option<Coin>,
 }
 
 #[cw_serde]
@@ -43,12 +233,25 @@ pub struct Service {
     pub dsources: Vec<DataSourceState>,
     pub tcases: Vec<TestCaseState>,
     pub oscript_url: String,
+    /// Base fee charged per request, on top of the data source fees.
+    pub fee: Option<Coin>,
+    /// Overrides the request timeout from the config for this service.
+    pub request_timeout: Option<u64>,
+    /// Hash function of the merkle trees of the service requests.
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
+    /// Format of the merkle trees of the service requests, `TreeFormat::V1` for new services.
+    /// Not set for services stored before tree formats, which use `TreeFormat::Legacy`.
+    pub tree_format: Option<TreeFormat>,
 }
 
 #[cw_serde]
 pub struct ServiceInfo {
     pub owner: Addr,
     pub service: Service,
+    /// Executors allowed to serve the service, all executors when not set.
+    #[serde(default)]
+    pub allowed_executors: Option<Vec<Addr>>,
 }
 
 pub fn store_service_info(
@@ -56,6 +259,12 @@ pub fn store_service_info(
     service_name: &[u8],
     service_info: &ServiceInfo,
 ) -> StdResult<()> {
+    if prefixed_read(storage, PREFIX_SERVICE_INFO)
+        .get(service_name)
+        .is_none()
+    {
+        count_update(storage, KEY_SERVICE_COUNT, |count| count + 1)?;
+    }
     bucket(storage, PREFIX_SERVICE_INFO).save(service_name, service_info)
 }
 
@@ -63,8 +272,15 @@ pub fn read_service_info(storage: &dyn Storage, service_name: &[u8]) -> StdResul
     bucket_read(storage, PREFIX_SERVICE_INFO).load(service_name)
 }
 
-pub fn remove_service_info(storage: &mut dyn Storage, service_name: &[u8]) {
-    bucket::<ServiceInfo>(storage, PREFIX_SERVICE_INFO).remove(service_name)
+pub fn remove_service_info(storage: &mut dyn Storage, service_name: &[u8]) -> StdResult<()> {
+    if prefixed_read(storage, PREFIX_SERVICE_INFO)
+        .get(service_name)
+        .is_some()
+    {
+        count_update(storage, KEY_SERVICE_COUNT, |count| count.saturating_sub(1))?;
+    }
+    bucket::<ServiceInfo>(storage, PREFIX_SERVICE_INFO).remove(service_name);
+    Ok(())
 }
 
 pub fn read_service_infos(
@@ -91,38 +307,376 @@ pub fn config_save(storage: &mut dyn Storage, config: &Config) -> StdResult<()>
     singleton(storage, KEY_CONFIG).save(config)
 }
 
-pub fn store_executor(storage: &mut dyn Storage, executor: CanonicalAddr) {
-    prefixed(storage, PREFIX_EXECUTOR).set(executor.as_slice(), &[0])
+pub fn store_executor(
+    storage: &mut dyn Storage,
+    executor: &CanonicalAddr,
+    executor_info: &Executor,
+) -> StdResult<()> {
+    let stored = executor_prefixes(storage).get(executor.as_slice());
+    if stored.is_none() {
+        count_update(storage, KEY_EXECUTOR_COUNT, |count| count + 1)?;
+    }
+    match (
+        is_stored_executor_active(stored.as_deref()),
+        executor_info.is_active,
+    ) {
+        (false, true) => {
+            count_update(storage, KEY_ACTIVE_EXECUTOR_COUNT, |count| count + 1)?;
+            store_executor_set_change(storage, executor, true)?;
+        }
+        (true, false) => {
+            count_update(storage, KEY_ACTIVE_EXECUTOR_COUNT, |count| {
+                count.saturating_sub(1)
+            })?;
+            store_executor_set_change(storage, executor, false)?;
+        }
+        _ => {}
+    }
+    bucket(storage, PREFIX_EXECUTOR).save(executor.as_slice(), executor_info)
+}
+
+/// Every change of the active executor set makes a new version, the history keeps per executor
+/// the versions from which it joined or left the set.
+fn store_executor_set_change(
+    storage: &mut dyn Storage,
+    executor: &CanonicalAddr,
+    is_active: bool,
+) -> StdResult<()> {
+    let version = executor_set_version_read(storage) + 1;
+    singleton(storage, KEY_EXECUTOR_SET_VERSION).save(&version)?;
+    Bucket::multilevel(storage, &[PREFIX_EXECUTOR_SET_HISTORY, executor.as_slice()])
+        .save(&version.to_be_bytes(), &is_active)
+}
+
+pub fn executor_set_version_read(storage: &dyn Storage) -> u64 {
+    count_read(storage, KEY_EXECUTOR_SET_VERSION)
 }
 
-pub fn read_executor(storage: &dyn Storage, executor: CanonicalAddr) -> Option<Vec<u8>> {
-    prefixed_read(storage, PREFIX_EXECUTOR).get(executor.as_slice())
+/// Whether the executor was in the active set at the given version.
+pub fn is_executor_in_set(storage: &dyn Storage, executor: &CanonicalAddr, version: u64) -> bool {
+    ReadonlyBucket::<bool>::multilevel(storage, &[PREFIX_EXECUTOR_SET_HISTORY, executor.as_slice()])
+        .range(
+            None,
+            Some(&version.saturating_add(1).to_be_bytes()),
+            Order::Descending,
+        )
+        .next()
+        .and_then(|change| change.ok())
+        .is_some_and(|(_, is_active)| is_active)
 }
 
-pub fn executor_prefixes(storage: &dyn Storage) -> cosmwasm_storage::ReadonlyPrefixedStorage {
+/// Executors active before the history existed join the set at a new version.
+pub fn migrate_executor_set(storage: &mut dyn Storage) -> StdResult<()> {
+    let executors: Vec<CanonicalAddr> = executor_prefixes(storage)
+        .range(None, None, Order::Ascending)
+        .filter(|(_, executor_info)| is_stored_executor_active(Some(executor_info)))
+        .map(|(executor, _)| CanonicalAddr::from(executor))
+        .collect();
+    for executor in executors {
+        let has_history = ReadonlyBucket::<bool>::multilevel(
+            storage,
+            &[PREFIX_EXECUTOR_SET_HISTORY, executor.as_slice()],
+        )
+        .range(None, None, Order::Ascending)
+        .next()
+        .is_some();
+        if !has_history {
+            store_executor_set_change(storage, &executor, true)?;
+        }
+    }
+    Ok(())
+}
+
+/// Legacy executor entries are not records and count as inactive until migrated.
+pub fn is_stored_executor_active(stored: Option<&[u8]>) -> bool {
+    stored.is_some_and(|executor_info| {
+        from_slice::<Executor>(executor_info).is_ok_and(|executor_info| executor_info.is_active)
+    })
+}
+
+pub fn read_executor(storage: &dyn Storage, executor: &CanonicalAddr) -> Option<Executor> {
+    bucket_read(storage, PREFIX_EXECUTOR)
+        .may_load(executor.as_slice())
+        .ok()
+        .flatten()
+}
+
+pub fn executor_prefixes(storage: &dyn Storage) -> cosmwasm_storage::ReadonlyPrefixedStorage<'_> {
     prefixed_read(storage, PREFIX_EXECUTOR)
 }
 
-pub fn remove_executor(storage: &mut dyn Storage, executor: CanonicalAddr) {
-    prefixed(storage, PREFIX_EXECUTOR).remove(executor.as_slice())
+pub fn remove_executor(storage: &mut dyn Storage, executor: &CanonicalAddr) -> StdResult<()> {
+    let stored = executor_prefixes(storage).get(executor.as_slice());
+    if stored.is_some() {
+        count_update(storage, KEY_EXECUTOR_COUNT, |count| count.saturating_sub(1))?;
+    }
+    if is_stored_executor_active(stored.as_deref()) {
+        count_update(storage, KEY_ACTIVE_EXECUTOR_COUNT, |count| {
+            count.saturating_sub(1)
+        })?;
+        store_executor_set_change(storage, executor, false)?;
+    }
+    prefixed(storage, PREFIX_EXECUTOR).remove(executor.as_slice());
+    Ok(())
+}
+
+pub fn read_executor_application(
+    storage: &dyn Storage,
+    applicant: &CanonicalAddr,
+) -> Option<ExecutorApplication> {
+    bucket_read(storage, PREFIX_EXECUTOR_APPLICATION)
+        .may_load(applicant.as_slice())
+        .ok()
+        .flatten()
+}
+
+pub fn read_executor_applications(
+    storage: &dyn Storage,
+    start: Option<&[u8]>,
+    limit: usize,
+) -> StdResult<Vec<(CanonicalAddr, ExecutorApplication)>> {
+    bucket_read(storage, PREFIX_EXECUTOR_APPLICATION)
+        .range(start, None, Order::Ascending)
+        .take(limit)
+        .map(
+            |application_result: StdResult<(Vec<u8>, ExecutorApplication)>| {
+                let (applicant, application) = application_result?;
+                Ok((CanonicalAddr::from(applicant), application))
+            },
+        )
+        .collect()
+}
+
+pub fn store_executor_application(
+    storage: &mut dyn Storage,
+    applicant: &CanonicalAddr,
+    application: &ExecutorApplication,
+) -> StdResult<()> {
+    bucket(storage, PREFIX_EXECUTOR_APPLICATION).save(applicant.as_slice(), application)
+}
+
+pub fn remove_executor_application(storage: &mut dyn Storage, applicant: &CanonicalAddr) {
+    bucket::<ExecutorApplication>(storage, PREFIX_EXECUTOR_APPLICATION).remove(applicant.as_slice())
+}
+
+pub fn read_executor_changes(storage: &dyn Storage) -> StdResult<Option<ExecutorChanges>> {
+    singleton_read(storage, KEY_EXECUTOR_CHANGES).may_load()
+}
+
+pub fn store_executor_changes(
+    storage: &mut dyn Storage,
+    executor_changes: &ExecutorChanges,
+) -> StdResult<()> {
+    singleton(storage, KEY_EXECUTOR_CHANGES).save(executor_changes)
+}
+
+pub fn remove_executor_changes(storage: &mut dyn Storage) {
+    singleton::<ExecutorChanges>(storage, KEY_EXECUTOR_CHANGES).remove()
+}
+
+/// Epoch of the height, every height is in epoch 0 when epochs are disabled.
+pub fn epoch_at(epoch_length: u64, height: u64) -> u64 {
+    height.checked_div(epoch_length).unwrap_or_default()
+}
+
+pub fn epoch_start_height(epoch_length: u64, epoch: u64) -> u64 {
+    epoch.saturating_mul(epoch_length)
+}
+
+pub fn read_executor_rewards(storage: &dyn Storage, executor: &CanonicalAddr) -> Vec<Coin> {
+    bucket_read(storage, PREFIX_EXECUTOR_REWARD)
+        .may_load(executor.as_slice())
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+pub fn store_executor_rewards(
+    storage: &mut dyn Storage,
+    executor: &CanonicalAddr,
+    rewards: &Vec<Coin>,
+) -> StdResult<()> {
+    bucket(storage, PREFIX_EXECUTOR_REWARD).save(executor.as_slice(), rewards)
+}
+
+pub fn remove_executor_rewards(storage: &mut dyn Storage, executor: &CanonicalAddr) {
+    bucket::<Vec<Coin>>(storage, PREFIX_EXECUTOR_REWARD).remove(executor.as_slice())
+}
+
+pub fn store_challenge(
+    storage: &mut dyn Storage,
+    stage: u64,
+    challenge: &Challenge,
+) -> StdResult<()> {
+    bucket(storage, PREFIX_CHALLENGE).save(&stage.to_be_bytes(), challenge)
+}
+
+pub fn read_challenge(storage: &dyn Storage, stage: u64) -> StdResult<Challenge> {
+    bucket_read(storage, PREFIX_CHALLENGE).load(&stage.to_be_bytes())
+}
+
+/// Executors who signed the given merkle root for a stage.
+pub fn read_merkle_root_signers(
+    storage: &dyn Storage,
+    stage: u64,
+    merkle_root: &[u8; 32],
+) -> Vec<Addr> {
+    bucket_read(storage, PREFIX_MERKLE_ROOT_SIGNERS)
+        .may_load(&[&stage.to_be_bytes()[..], merkle_root].concat())
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+pub fn store_merkle_root_signers(
+    storage: &mut dyn Storage,
+    stage: u64,
+    merkle_root: &[u8; 32],
+    signers: &Vec<Addr>,
+) -> StdResult<()> {
+    bucket(storage, PREFIX_MERKLE_ROOT_SIGNERS)
+        .save(&[&stage.to_be_bytes()[..], merkle_root].concat(), signers)
+}
+
+/// Removes the signers of every merkle root submitted for the stage.
+pub fn remove_merkle_root_signers(storage: &mut dyn Storage, stage: u64) {
+    let merkle_roots: Vec<Vec<u8>> = prefixed_read(storage, PREFIX_MERKLE_ROOT_SIGNERS)
+        .range(
+            Some(&stage.to_be_bytes()),
+            Some(&(stage + 1).to_be_bytes()),
+            Order::Ascending,
+        )
+        .map(|(key, _)| key)
+        .collect();
+    let mut signers = prefixed(storage, PREFIX_MERKLE_ROOT_SIGNERS);
+    for key in merkle_roots {
+        signers.remove(&key);
+    }
+}
+
+/// Results proven against the merkle root of a stage, in submission order.
+pub fn read_request_results(storage: &dyn Storage, stage: u64) -> Vec<Binary> {
+    bucket_read(storage, PREFIX_REQUEST_RESULT)
+        .may_load(&stage.to_be_bytes())
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+pub fn store_request_results(
+    storage: &mut dyn Storage,
+    stage: u64,
+    results: &Vec<Binary>,
+) -> StdResult<()> {
+    bucket(storage, PREFIX_REQUEST_RESULT).save(&stage.to_be_bytes(), results)
+}
+
+pub fn remove_request_results(storage: &mut dyn Storage, stage: u64) {
+    bucket::<Vec<Binary>>(storage, PREFIX_REQUEST_RESULT).remove(&stage.to_be_bytes())
+}
+
+pub fn read_executor_bond(storage: &dyn Storage, executor: &CanonicalAddr) -> ExecutorBond {
+    bucket_read(storage, PREFIX_EXECUTOR_BOND)
+        .may_load(executor.as_slice())
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+pub fn store_executor_bond(
+    storage: &mut dyn Storage,
+    executor: &CanonicalAddr,
+    bond: &ExecutorBond,
+) -> StdResult<()> {
+    bucket(storage, PREFIX_EXECUTOR_BOND).save(executor.as_slice(), bond)
+}
+
+/// Whether any executor has funds bonded or unbonding.
+pub fn has_executor_bonds(storage: &dyn Storage) -> bool {
+    bucket_read::<ExecutorBond>(storage, PREFIX_EXECUTOR_BOND)
+        .range(None, None, Order::Ascending)
+        .any(|bond| bond.is_ok_and(|(_, bond)| !bond.is_empty()))
+}
+
+/// Bonds used to be stored without denom, they were deposited in the configured bond denom.
+pub fn migrate_executor_bonds(storage: &mut dyn Storage) -> StdResult<()> {
+    let Some(bond_denom) = config_read(storage)?
+        .min_executor_bond
+        .map(|min_executor_bond| min_executor_bond.denom)
+    else {
+        return Ok(());
+    };
+    let bonds = bucket_read::<ExecutorBond>(storage, PREFIX_EXECUTOR_BOND)
+        .range(None, None, Order::Ascending)
+        .collect::<StdResult<Vec<(Vec<u8>, ExecutorBond)>>>()?;
+    for (executor, mut bond) in bonds {
+        if !bond.denom.is_empty() {
+            continue;
+        }
+        bond.denom = bond_denom.clone();
+        for unbonding in bond.unbonding.iter_mut() {
+            unbonding.denom = bond_denom.clone();
+        }
+        bucket(storage, PREFIX_EXECUTOR_BOND).save(&executor, &bond)?;
+    }
+    Ok(())
 }
 
 pub fn config_update(
     storage: &mut dyn Storage,
-    new_owner: Option<Addr>,
-    new_max_req_threshold: Option<u64>,
+    update_config_msg: &UpdateConfigMsg,
 ) -> StdResult<Config> {
     let mut config = config_read(storage)?;
     let mut need_update = false;
 
-    if let Some(new_owner) = new_owner {
-        config.owner = new_owner;
+    if let Some(new_owner) = &update_config_msg.new_owner {
+        config.owner = new_owner.clone();
         need_update = true;
     }
-    if let Some(max_req_threshold) = new_max_req_threshold {
+    if let Some(max_req_threshold) = update_config_msg.new_max_req_threshold {
         config.max_req_threshold = max_req_threshold;
         need_update = true;
     }
+    if let Some(request_timeout) = update_config_msg.new_request_timeout {
+        config.request_timeout = Some(request_timeout);
+        need_update = true;
+    }
+    if let Some(challenge_period) = update_config_msg.new_challenge_period {
+        config.challenge_period = challenge_period;
+        need_update = true;
+    }
+    if let Some(challenge_deposit) = &update_config_msg.new_challenge_deposit {
+        config.challenge_deposit = Some(challenge_deposit.clone());
+        need_update = true;
+    }
+    if let Some(min_executor_bond) = &update_config_msg.new_min_executor_bond {
+        config.min_executor_bond = Some(min_executor_bond.clone());
+        need_update = true;
+    }
+    if let Some(unbonding_period) = update_config_msg.new_unbonding_period {
+        config.unbonding_period = unbonding_period;
+        need_update = true;
+    }
+    if let Some(slash_fraction) = update_config_msg.new_slash_fraction {
+        config.slash_fraction = slash_fraction;
+        need_update = true;
+    }
+    if let Some(jail_period) = update_config_msg.new_jail_period {
+        config.jail_period = jail_period;
+        need_update = true;
+    }
+    if let Some(burn_slashed) = update_config_msg.new_burn_slashed {
+        config.burn_slashed = burn_slashed;
+        need_update = true;
+    }
+    if let Some(owner_registration) = update_config_msg.new_owner_registration {
+        config.owner_registration = owner_registration;
+        need_update = true;
+    }
+    if let Some(epoch_length) = update_config_msg.new_epoch_length {
+        config.epoch_length = epoch_length;
+        need_update = true;
+    }
     if need_update {
         config_save(storage, &config)?;
     }
@@ -133,6 +687,25 @@ pub fn config_read(storage: &dyn Storage) -> StdResult<Config> {
     singleton_read(storage, KEY_CONFIG).load()
 }
 
+/// Stored config flags, none for configs stored before the flag existed.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "cosmwasm_schema::serde")]
+struct StoredConfigFlags {
+    owner_registration: Option<bool>,
+}
+
+/// The owner registered the merkle roots of contracts stored before owner registration could be
+/// turned off, they keep doing so until the owner turns it off.
+pub fn migrate_config(storage: &mut dyn Storage) -> StdResult<()> {
+    let flags: StoredConfigFlags = singleton_read(storage, KEY_CONFIG).load()?;
+    if flags.owner_registration.is_none() {
+        let mut config = config_read(storage)?;
+        config.owner_registration = true;
+        config_save(storage, &config)?;
+    }
+    Ok(())
+}
+
 pub fn latest_stage_read(storage: &dyn Storage) -> StdResult<u64> {
     singleton_read(storage, KEY_LATEST_STAGE).load()
 }
@@ -145,17 +718,150 @@ pub fn latest_stage_update(storage: &mut dyn Storage) -> StdResult<u64> {
     singleton(storage, KEY_LATEST_STAGE).update(|latest_stage: u64| Ok(latest_stage + 1))
 }
 
+// counters kept along the stored items so totals do not need a full range
+
+fn count_read(storage: &dyn Storage, key: &[u8]) -> u64 {
+    singleton_read(storage, key)
+        .may_load()
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+fn count_update(
+    storage: &mut dyn Storage,
+    key: &[u8],
+    update: impl Fn(u64) -> u64,
+) -> StdResult<()> {
+    let count = update(count_read(storage, key));
+    singleton(storage, key).save(&count)
+}
+
+pub fn service_count_read(storage: &dyn Storage) -> u64 {
+    count_read(storage, KEY_SERVICE_COUNT)
+}
+
+pub fn executor_count_read(storage: &dyn Storage) -> u64 {
+    count_read(storage, KEY_EXECUTOR_COUNT)
+}
+
+pub fn active_executor_count_read(storage: &dyn Storage) -> u64 {
+    count_read(storage, KEY_ACTIVE_EXECUTOR_COUNT)
+}
+
+pub fn service_request_count_read(storage: &dyn Storage, service: &str) -> u64 {
+    bucket_read(storage, PREFIX_SERVICE_REQUEST_COUNT)
+        .may_load(service.as_bytes())
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+pub fn service_request_count_save(
+    storage: &mut dyn Storage,
+    service: &str,
+    count: u64,
+) -> StdResult<()> {
+    bucket(storage, PREFIX_SERVICE_REQUEST_COUNT).save(service.as_bytes(), &count)
+}
+
+/// Number of unresolved challenges against merkle roots the executor registered.
+pub fn open_challenge_count_read(storage: &dyn Storage, executor: &CanonicalAddr) -> u64 {
+    bucket_read(storage, PREFIX_EXECUTOR_OPEN_CHALLENGES)
+        .may_load(executor.as_slice())
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+pub fn open_challenge_count_save(
+    storage: &mut dyn Storage,
+    executor: &CanonicalAddr,
+    count: u64,
+) -> StdResult<()> {
+    if count == 0 {
+        bucket::<u64>(storage, PREFIX_EXECUTOR_OPEN_CHALLENGES).remove(executor.as_slice());
+        return Ok(());
+    }
+    bucket(storage, PREFIX_EXECUTOR_OPEN_CHALLENGES).save(executor.as_slice(), &count)
+}
+
+pub fn requester_request_count_read(storage: &dyn Storage, requester: &Addr) -> u64 {
+    bucket_read(storage, PREFIX_REQUESTER_REQUEST_COUNT)
+        .may_load(requester.as_bytes())
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+pub fn requester_request_count_save(
+    storage: &mut dyn Storage,
+    requester: &Addr,
+    count: u64,
+) -> StdResult<()> {
+    bucket(storage, PREFIX_REQUESTER_REQUEST_COUNT).save(requester.as_bytes(), &count)
+}
+
+/// Counts the stored services and executors again, for contracts stored before the counters.
+pub fn migrate_counters(storage: &mut dyn Storage) -> StdResult<()> {
+    let services = prefixed_read(storage, PREFIX_SERVICE_INFO)
+        .range(None, None, Order::Ascending)
+        .count() as u64;
+    singleton(storage, KEY_SERVICE_COUNT).save(&services)?;
+    let (executors, active_executors) = executor_prefixes(storage)
+        .range(None, None, Order::Ascending)
+        .fold(
+            (0u64, 0u64),
+            |(executors, active_executors), (_, executor_info)| {
+                let is_active = is_stored_executor_active(Some(&executor_info));
+                (executors + 1, active_executors + is_active as u64)
+            },
+        );
+    singleton(storage, KEY_EXECUTOR_COUNT).save(&executors)?;
+    singleton(storage, KEY_ACTIVE_EXECUTOR_COUNT).save(&active_executors)
+}
+
+/// Progress of the backfill of requests stored before their status, service and requester counts.
+#[cw_serde]
+pub struct RequestMigration {
+    pub next_stage: u64,
+    pub last_stage: u64,
+}
+
+pub fn request_migration_read(storage: &dyn Storage) -> StdResult<Option<RequestMigration>> {
+    singleton_read(storage, KEY_REQUEST_MIGRATION).may_load()
+}
+
+/// Whether the requests stored before statuses are all in the status index.
+pub fn are_requests_migrated(storage: &dyn Storage) -> StdResult<bool> {
+    Ok(request_migration_read(storage)?
+        .is_none_or(|migration| migration.next_stage > migration.last_stage))
+}
+
+pub fn request_migration_save(
+    storage: &mut dyn Storage,
+    request_migration: &RequestMigration,
+) -> StdResult<()> {
+    singleton(storage, KEY_REQUEST_MIGRATION).save(request_migration)
+}
+
 // indexes requests
 // for structures
 pub struct RequestIndexes<'a> {
     pub service: MultiIndex<'a, Vec<u8>, Request, u64>,
     pub merkle_root: MultiIndex<'a, Vec<u8>, Request, u64>,
     pub requester: MultiIndex<'a, Vec<u8>, Request, u64>,
+    pub status: MultiIndex<'a, Vec<u8>, Request, u64>,
 }
 
 impl<'a> IndexList<Request> for RequestIndexes<'a> {
     fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Request>> + '_> {
-        let v: Vec<&dyn Index<Request>> = vec![&self.service, &self.merkle_root, &self.requester];
+        let v: Vec<&dyn Index<Request>> = vec![
+            &self.service,
+            &self.merkle_root,
+            &self.requester,
+            &self.status,
+        ];
         Box::new(v.into_iter())
     }
 }
@@ -178,13 +884,27 @@ pub fn requests<'a>() -> IndexedMap<'a, u64, Request, RequestIndexes<'a>> {
             "requests",
             "requests_requester",
         ),
+        status: MultiIndex::new(
+            |_pk, d| d.status.as_str().as_bytes().to_vec(),
+            "requests",
+            "requests_status",
+        ),
     };
     IndexedMap::new("requests", indexes)
 }
 
 // settings for pagination
 pub const MAX_LIMIT: u8 = 50;
+/// Bounds on the results stored per stage.
+pub const MAX_RESULT_SIZE: usize = 1024;
+pub const MAX_RESULTS: usize = 10;
+/// Items a `VerifyDataBatch` query verifies at most.
+pub const MAX_VERIFY_BATCH: usize = 100;
 pub const DEFAULT_LIMIT: u8 = 20;
+/// Requests a search goes through at most before returning a page.
+pub const MAX_SEARCH_SCAN: usize = 500;
+/// Requests migrated by a `MigrateRequests` call when no limit is given.
+pub const DEFAULT_MIGRATE_LIMIT: u64 = 100;
 
 pub fn get_range_params<'a, T: Bounder<'a>>(
     offset: Option<T>,
@@ -215,6 +935,20 @@ pub fn get_range_params<'a, T: Bounder<'a>>(
     (limit, min, max, order_enum)
 }
 
+/// Bounds of a bucket range resuming after the given key, in either order.
+pub fn range_after(
+    start: Option<Vec<u8>>,
+    end: Option<Vec<u8>>,
+    start_after: Option<Vec<u8>>,
+    order: Order,
+) -> (Option<Vec<u8>>, Option<Vec<u8>>) {
+    match (start_after, order) {
+        (Some(start_after), Order::Ascending) => (Some([start_after, vec![0]].concat()), end),
+        (Some(start_after), Order::Descending) => (start, Some(start_after)),
+        (None, _) => (start, end),
+    }
+}
+
 pub fn match_order(order: Option<u8>) -> Order {
     match order {
         Some(1) => Order::Ascending,
@@ -225,5 +959,21 @@ pub fn match_order(order: Option<u8>) -> Order {
 
 pub static KEY_CONFIG: &[u8] = b"config";
 pub static KEY_LATEST_STAGE: &[u8] = b"latest_stage";
+pub static KEY_REQUEST_MIGRATION: &[u8] = b"request_migration";
+pub static KEY_SERVICE_COUNT: &[u8] = b"service_count";
+pub static KEY_EXECUTOR_COUNT: &[u8] = b"executor_count";
+pub static KEY_ACTIVE_EXECUTOR_COUNT: &[u8] = b"active_executor_count";
+pub static KEY_EXECUTOR_SET_VERSION: &[u8] = b"executor_set_version";
+pub static KEY_EXECUTOR_CHANGES: &[u8] = b"executor_changes";
+pub static PREFIX_EXECUTOR_SET_HISTORY: &[u8] = b"executor_set_history";
+pub static PREFIX_SERVICE_REQUEST_COUNT: &[u8] = b"service_request_count";
+pub static PREFIX_REQUESTER_REQUEST_COUNT: &[u8] = b"requester_request_count";
 pub static PREFIX_SERVICE_INFO: &[u8] = b"service_info";
 pub static PREFIX_EXECUTOR: &[u8] = b"executor";
+pub static PREFIX_EXECUTOR_REWARD: &[u8] = b"executor_reward";
+pub static PREFIX_EXECUTOR_APPLICATION: &[u8] = b"executor_application";
+pub static PREFIX_CHALLENGE: &[u8] = b"challenge";
+pub static PREFIX_EXECUTOR_OPEN_CHALLENGES: &[u8] = b"executor_open_challenges";
+pub static PREFIX_EXECUTOR_BOND: &[u8] = b"executor_bond";
+pub static PREFIX_MERKLE_ROOT_SIGNERS: &[u8] = b"merkle_root_signers";
+pub static PREFIX_REQUEST_RESULT: &[u8] = b"request_result";